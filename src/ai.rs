@@ -0,0 +1,125 @@
+use std::collections::HashSet;
+
+use components::MoveAction;
+use pathfinding::{PathGrid, Pathfinder};
+
+const MOVES: [MoveAction; 4] = [MoveAction::Up, MoveAction::Right, MoveAction::Down, MoveAction::Left];
+
+/// How a creature picks its moves. Levels give each creature one of these
+/// as a table with a `type`, e.g. `{ type = "wander" }`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Behaviour {
+    /// Walks from one tile of `route` to the next, starting over at the end.
+    Patrol { route: Vec<(i32, i32)> },
+    /// Steps in a random direction, or stays put now and then.
+    Wander,
+    /// Heads for the player while they're within `range` tiles.
+    Chase { range: u32 },
+    /// Heads for the nearest plant within `range` tiles.
+    Graze { range: u32 },
+}
+
+/// What a creature knows about the level when picking its move.
+pub struct View<'a> {
+    /// The ground creatures can walk on, without walls, blocks or water.
+    pub grid: &'a PathGrid,
+    /// Tiles taken by the player and other creatures.
+    pub movers: &'a HashSet<(i32, i32)>,
+    pub player: Option<(i32, i32)>,
+    pub plants: &'a [(i32, i32)],
+}
+
+impl<'a> View<'a> {
+    /// Creatures don't push blocks and don't walk into each other or the
+    /// player.
+    pub fn is_walkable(&self, position: (i32, i32)) -> bool {
+        self.grid.cost(position).is_some() && !self.movers.contains(&position)
+    }
+}
+
+pub fn distance(a: (i32, i32), b: (i32, i32)) -> u32 {
+    ((a.0 - b.0).abs() + (a.1 - b.1).abs()) as u32
+}
+
+fn step(position: (i32, i32), action: MoveAction) -> (i32, i32) {
+    let (dx, dy) = action.direction();
+    (position.0 + dx, position.1 + dy)
+}
+
+/// The first move along the way to `goal`, going around other movers. None
+/// if there's no way there, or the goal itself is taken and the creature is
+/// already next to it.
+pub fn step_towards(
+    position: (i32, i32),
+    goal: (i32, i32),
+    view: &View,
+    pathfinder: &mut Pathfinder,
+) -> Option<MoveAction> {
+    let path = pathfinder.find_avoiding(view.grid, position, goal, view.movers)?;
+
+    match path.first() {
+        Some(&action) if view.is_walkable(step(position, action)) => Some(action),
+        _ => None,
+    }
+}
+
+/// Scrambles `seed` so consecutive turns and entities give unrelated
+/// numbers. Wandering is driven by the turn rather than a random number
+/// generator, so an undone turn plays out the same way again.
+fn scramble(seed: u32) -> u32 {
+    let mut x = seed.wrapping_mul(0x9E37_79B9) ^ 0x5bd1_e995;
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x2c1b_3c6d);
+    x ^= x >> 12;
+    x
+}
+
+/// Picks a creature's move for this turn. `waypoint` is the patrol's next
+/// stop and is advanced when reached; `seed` varies per turn and creature.
+pub fn choose_move(
+    behaviour: &Behaviour,
+    waypoint: &mut usize,
+    position: (i32, i32),
+    view: &View,
+    pathfinder: &mut Pathfinder,
+    seed: u32,
+) -> Option<MoveAction> {
+    match *behaviour {
+        Behaviour::Patrol { ref route } => {
+            if route.is_empty() {
+                return None;
+            }
+
+            if route[*waypoint % route.len()] == position {
+                *waypoint = (*waypoint + 1) % route.len();
+            }
+
+            step_towards(position, route[*waypoint % route.len()], view, pathfinder)
+        }
+        Behaviour::Wander => {
+            let roll = scramble(seed) as usize % (MOVES.len() + 1);
+            let action = match MOVES.get(roll) {
+                Some(&action) => action,
+                None => return None,
+            };
+
+            if view.is_walkable(step(position, action)) {
+                Some(action)
+            } else {
+                None
+            }
+        }
+        Behaviour::Chase { range } => match view.player {
+            Some(player) if distance(position, player) <= range => step_towards(position, player, view, pathfinder),
+            _ => None,
+        },
+        Behaviour::Graze { range } => view
+            .plants
+            .iter()
+            .cloned()
+            .filter(|&plant| distance(position, plant) <= range)
+            .min_by_key(|&plant| (distance(position, plant), plant.1, plant.0))
+            .and_then(|plant| step_towards(position, plant, view, pathfinder)),
+    }
+}
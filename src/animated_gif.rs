@@ -0,0 +1,150 @@
+use std::time::Duration;
+
+use ggez::graphics::{self, FilterMode};
+use ggez::{Context, GameResult};
+use gif::{self, SetParameter};
+use image::{Rgba, RgbaImage};
+use warmy;
+
+use animation::{AnimationClip, PlaybackDirection};
+use assets::{self, AssetError};
+
+/// Browsers treat a delay of 0 as "as fast as possible" but in practice
+/// slow it down to about this much.
+const DEFAULT_FRAME_DELAY: u64 = 100;
+
+/// An animated GIF, decoded into a horizontal strip of equally sized frames
+/// and a clip that plays them with the GIF's delays.
+#[derive(Debug, Clone)]
+pub struct AnimatedGif {
+    pub strip: graphics::Image,
+    pub frame_width: u32,
+    pub frame_height: u32,
+    pub frame_count: u32,
+    pub clip: AnimationClip,
+}
+
+impl AnimatedGif {
+    /// A single frame of the missing texture, for GIFs that failed to load.
+    pub fn placeholder(ctx: &mut Context) -> GameResult<AnimatedGif> {
+        let strip = assets::missing_texture(ctx)?;
+
+        Ok(AnimatedGif {
+            frame_width: strip.width(),
+            frame_height: strip.height(),
+            strip,
+            frame_count: 1,
+            clip: AnimationClip::from_range("missing", 0, 0, PlaybackDirection::Forward, &[]),
+        })
+    }
+
+    /// Source rectangle of a frame within the strip.
+    pub fn src(&self, frame: u32) -> graphics::Rect {
+        let frame_count = self.frame_count.max(1);
+
+        graphics::Rect::new(
+            (frame % frame_count) as f32 / frame_count as f32,
+            0.0,
+            1.0 / frame_count as f32,
+            1.0,
+        )
+    }
+}
+
+impl warmy::Load<Context> for AnimatedGif {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let bytes = assets::read_bytes(&key, store.root(), ctx)?;
+        let (frames, durations) = decode(&bytes).map_err(|e| AssetError::decode(key.as_path(), e))?;
+
+        let frame_count = frames.len() as u32;
+        let (frame_width, frame_height) = frames
+            .first()
+            .map(|frame| frame.dimensions())
+            .unwrap_or((1, 1));
+
+        let mut strip = RgbaImage::new(frame_width * frame_count.max(1), frame_height);
+
+        for (index, frame) in frames.iter().enumerate() {
+            for (x, y, pixel) in frame.enumerate_pixels() {
+                strip.put_pixel(index as u32 * frame_width + x, y, *pixel);
+            }
+        }
+
+        let (strip_width, strip_height) = strip.dimensions();
+        let mut image = graphics::Image::from_rgba8(ctx, strip_width as u16, strip_height as u16, &strip)
+            .map_err(|e| AssetError::decode(key.as_path(), e))?;
+        image.set_filter(FilterMode::Nearest);
+
+        let name = key.as_path().file_stem().and_then(|stem| stem.to_str()).unwrap_or("gif");
+        let clip = AnimationClip::from_range(
+            name,
+            0,
+            frame_count.saturating_sub(1),
+            PlaybackDirection::Forward,
+            &durations,
+        );
+
+        Ok(warmy::Loaded::from(AnimatedGif {
+            strip: image,
+            frame_width,
+            frame_height,
+            frame_count,
+            clip,
+        }))
+    }
+}
+
+/// Plays back the GIF onto a canvas, honouring each frame's disposal, and
+/// returns a copy of the canvas after every frame.
+fn decode(bytes: &[u8]) -> Result<(Vec<RgbaImage>, Vec<Duration>), gif::DecodingError> {
+    let mut decoder = gif::Decoder::new(bytes);
+    decoder.set(gif::ColorOutput::RGBA);
+
+    let mut reader = decoder.read_info()?;
+
+    let (width, height) = (u32::from(reader.width()), u32::from(reader.height()));
+    let mut canvas = RgbaImage::new(width, height);
+    let mut frames = Vec::new();
+    let mut durations = Vec::new();
+
+    while let Some(frame) = reader.read_next_frame()? {
+        let previous = canvas.clone();
+        let (left, top) = (u32::from(frame.left), u32::from(frame.top));
+        let frame_width = u32::from(frame.width);
+
+        for (index, pixel) in frame.buffer.chunks(4).enumerate() {
+            let (x, y) = (left + index as u32 % frame_width, top + index as u32 / frame_width);
+
+            if pixel.len() == 4 && pixel[3] != 0 && x < width && y < height {
+                canvas.put_pixel(x, y, Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]));
+            }
+        }
+
+        frames.push(canvas.clone());
+        durations.push(match frame.delay {
+            0 => Duration::from_millis(DEFAULT_FRAME_DELAY),
+            delay => Duration::from_millis(u64::from(delay) * 10),
+        });
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => {
+                for y in top..(top + u32::from(frame.height)).min(height) {
+                    for x in left..(left + frame_width).min(width) {
+                        canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                    }
+                }
+            }
+            gif::DisposalMethod::Previous => canvas = previous,
+            _ => {}
+        }
+    }
+
+    Ok((frames, durations))
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PlaybackDirection {
+    Forward,
+    Reverse,
+    PingPong,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ClipFrame {
+    pub frame: u32,
+    pub duration: Duration,
+}
+
+/// A named sequence of frames from a sprite sheet, e.g. a tag in an
+/// Aseprite file.
+#[derive(Debug, Clone)]
+pub struct AnimationClip {
+    pub name: String,
+    pub frames: Vec<ClipFrame>,
+}
+
+impl AnimationClip {
+    /// Builds a clip over the frames `from..=to`, taking each frame's
+    /// duration from `durations` and spelling out the playback direction as
+    /// an explicit frame order.
+    pub fn from_range(
+        name: &str,
+        from: u32,
+        to: u32,
+        direction: PlaybackDirection,
+        durations: &[Duration],
+    ) -> AnimationClip {
+        let (from, to) = (from.min(to), from.max(to));
+        let forward: Vec<u32> = (from..to + 1).collect();
+
+        let order: Vec<u32> = match direction {
+            PlaybackDirection::Forward => forward,
+            PlaybackDirection::Reverse => forward.into_iter().rev().collect(),
+            PlaybackDirection::PingPong => {
+                let back: Vec<u32> = if forward.len() > 2 {
+                    forward[1..forward.len() - 1].iter().rev().cloned().collect()
+                } else {
+                    Vec::new()
+                };
+
+                forward.into_iter().chain(back).collect()
+            }
+        };
+
+        let frames = order
+            .into_iter()
+            .map(|frame| ClipFrame {
+                frame,
+                duration: durations.get(frame as usize).cloned().unwrap_or(Duration::from_millis(100)),
+            })
+            .collect();
+
+        AnimationClip {
+            name: name.to_string(),
+            frames,
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().fold(Duration::from_millis(0), |total, frame| total + frame.duration)
+    }
+
+    /// The sheet frame to show `elapsed` after the clip started. Clips that
+    /// don't loop hold their last frame.
+    pub fn frame_at(&self, elapsed: Duration, looping: bool) -> u32 {
+        let total = duration_to_millis(self.duration());
+
+        if total == 0 {
+            return self.frames.first().map(|frame| frame.frame).unwrap_or(0);
+        }
+
+        let mut elapsed = duration_to_millis(elapsed);
+
+        if looping {
+            elapsed %= total;
+        }
+
+        for frame in self.frames.iter() {
+            let duration = duration_to_millis(frame.duration);
+
+            if elapsed < duration {
+                return frame.frame;
+            }
+
+            elapsed -= duration;
+        }
+
+        self.frames.last().map(|frame| frame.frame).unwrap_or(0)
+    }
+}
+
+fn duration_to_millis(duration: Duration) -> u64 {
+    duration.as_secs() * 1000 + u64::from(duration.subsec_millis())
+}
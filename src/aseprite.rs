@@ -0,0 +1,695 @@
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+use std::time::Duration;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use ggez::Context;
+use image::{Rgba, RgbaImage};
+use inflate;
+use warmy;
+
+use animation::{AnimationClip, PlaybackDirection};
+use assets::{self, AssetError};
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const HEADER_SIZE: u64 = 128;
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+const CHUNK_LAYER: u16 = 0x2004;
+const CHUNK_CEL: u16 = 0x2005;
+const CHUNK_TAGS: u16 = 0x2018;
+const CHUNK_PALETTE: u16 = 0x2019;
+
+const CEL_RAW: u16 = 0;
+const CEL_LINKED: u16 = 1;
+const CEL_COMPRESSED: u16 = 2;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_TYPE_GROUP: u16 = 1;
+const HEADER_FLAG_LAYER_OPACITY: u32 = 1;
+
+#[derive(Debug, Clone)]
+pub struct AsepriteLayer {
+    pub name: String,
+    /// False if the layer or any group it's nested in is hidden.
+    pub visible: bool,
+    pub opacity: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct AsepriteFrame {
+    pub image: RgbaImage,
+    pub duration: Duration,
+}
+
+/// An `.aseprite` file, read directly so art doesn't need exporting to PNG
+/// first. Visible layers are flattened into one image per frame and tags
+/// become animation clips; see Aseprite's `docs/ase-file-specs.md`.
+#[derive(Debug, Clone)]
+pub struct Aseprite {
+    pub width: u32,
+    pub height: u32,
+    pub layers: Vec<AsepriteLayer>,
+    pub frames: Vec<AsepriteFrame>,
+    pub clips: Vec<AnimationClip>,
+    pub generation: usize,
+}
+
+impl Aseprite {
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    pub fn parse(bytes: &[u8]) -> io::Result<Aseprite> {
+        Parser::new(bytes).parse()
+    }
+}
+
+impl warmy::Load<Context> for Aseprite {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let bytes = assets::read_bytes(&key, store.root(), ctx)?;
+
+        Aseprite::parse(&bytes)
+            .map(warmy::Loaded::from)
+            .map_err(|e| AssetError::decode(key.as_path(), e))
+    }
+}
+
+/// Name, first frame, last frame and direction of a tag.
+type Tag = (String, u32, u32, PlaybackDirection);
+
+#[derive(Clone)]
+struct Cel {
+    layer: usize,
+    x: i32,
+    y: i32,
+    opacity: u8,
+    image: RgbaImage,
+}
+
+struct Parser<'a> {
+    cursor: Cursor<&'a [u8]>,
+    width: u32,
+    height: u32,
+    color_depth: u16,
+    transparent_index: u8,
+    layer_opacity: bool,
+    palette: Vec<Rgba<u8>>,
+    layers: Vec<AsepriteLayer>,
+    /// Indices of the enclosing group layers, by nesting level.
+    groups: Vec<usize>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Parser {
+            cursor: Cursor::new(bytes),
+            width: 0,
+            height: 0,
+            color_depth: 32,
+            transparent_index: 0,
+            layer_opacity: false,
+            palette: Vec::new(),
+            layers: Vec::new(),
+            groups: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> io::Result<Aseprite> {
+        let _file_size = self.cursor.read_u32::<LittleEndian>()?;
+
+        if self.cursor.read_u16::<LittleEndian>()? != HEADER_MAGIC {
+            return Err(invalid_data("not an Aseprite file"));
+        }
+
+        let frame_count = self.cursor.read_u16::<LittleEndian>()?;
+        self.width = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+        self.height = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+        self.color_depth = self.cursor.read_u16::<LittleEndian>()?;
+        self.layer_opacity = self.cursor.read_u32::<LittleEndian>()? & HEADER_FLAG_LAYER_OPACITY != 0;
+
+        // Deprecated speed, then two reserved dwords.
+        self.skip(2 + 4 + 4)?;
+        self.transparent_index = self.cursor.read_u8()?;
+
+        if self.color_depth != 32 && self.color_depth != 16 && self.color_depth != 8 {
+            return Err(invalid_data("unsupported color depth"));
+        }
+
+        self.cursor.seek(SeekFrom::Start(HEADER_SIZE))?;
+
+        let mut cels: Vec<Vec<Cel>> = Vec::new();
+        let mut durations = Vec::new();
+        let mut tags = Vec::new();
+
+        for _ in 0..frame_count {
+            let (frame_cels, duration, frame_tags) = self.parse_frame(&cels)?;
+
+            cels.push(frame_cels);
+            durations.push(duration);
+            tags.extend(frame_tags);
+        }
+
+        // Tags come before most frames, so clips can only be built once all
+        // frame durations are known.
+        let clips = tags
+            .into_iter()
+            .map(|(name, from, to, direction)| AnimationClip::from_range(&name, from, to, direction, &durations))
+            .collect();
+
+        let frames = cels
+            .iter()
+            .zip(durations.iter())
+            .map(|(frame_cels, duration)| AsepriteFrame {
+                image: self.flatten(frame_cels),
+                duration: *duration,
+            })
+            .collect();
+
+        Ok(Aseprite {
+            width: self.width,
+            height: self.height,
+            layers: self.layers,
+            frames,
+            clips,
+            generation: assets::next_generation(),
+        })
+    }
+
+    fn parse_frame(&mut self, previous_frames: &[Vec<Cel>]) -> io::Result<(Vec<Cel>, Duration, Vec<Tag>)> {
+        let frame_start = self.cursor.position();
+        let frame_size = u64::from(self.cursor.read_u32::<LittleEndian>()?);
+
+        if self.cursor.read_u16::<LittleEndian>()? != FRAME_MAGIC {
+            return Err(invalid_data("bad frame header"));
+        }
+
+        let old_chunk_count = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+        let duration = Duration::from_millis(u64::from(self.cursor.read_u16::<LittleEndian>()?));
+        self.skip(2)?;
+        let new_chunk_count = self.cursor.read_u32::<LittleEndian>()?;
+
+        let chunk_count = if new_chunk_count == 0 {
+            old_chunk_count
+        } else {
+            new_chunk_count
+        };
+
+        let mut cels = Vec::new();
+        let mut tags = Vec::new();
+
+        for _ in 0..chunk_count {
+            let chunk_start = self.cursor.position();
+            let chunk_size = u64::from(self.cursor.read_u32::<LittleEndian>()?);
+            let chunk_type = self.cursor.read_u16::<LittleEndian>()?;
+            let chunk_end = chunk_start + chunk_size;
+
+            match chunk_type {
+                CHUNK_LAYER => self.parse_layer()?,
+                CHUNK_CEL => {
+                    if let Some(cel) = self.parse_cel(chunk_end, previous_frames)? {
+                        cels.push(cel);
+                    }
+                }
+                CHUNK_TAGS => tags = self.parse_tags()?,
+                CHUNK_PALETTE => self.parse_palette()?,
+                _ => {}
+            }
+
+            self.cursor.seek(SeekFrom::Start(chunk_end))?;
+        }
+
+        self.cursor.seek(SeekFrom::Start(frame_start + frame_size))?;
+        cels.sort_by_key(|cel| cel.layer);
+
+        Ok((cels, duration, tags))
+    }
+
+    fn parse_layer(&mut self) -> io::Result<()> {
+        let flags = self.cursor.read_u16::<LittleEndian>()?;
+        let layer_type = self.cursor.read_u16::<LittleEndian>()?;
+        let child_level = self.cursor.read_u16::<LittleEndian>()? as usize;
+
+        // Default width and height, then blend mode; only normal blending is
+        // supported.
+        self.skip(2 + 2 + 2)?;
+        let opacity = self.cursor.read_u8()?;
+        self.skip(3)?;
+        let name = self.read_string()?;
+
+        self.groups.truncate(child_level);
+
+        let parent_visible = self
+            .groups
+            .last()
+            .map(|&group| self.layers[group].visible)
+            .unwrap_or(true);
+
+        let index = self.layers.len();
+
+        self.layers.push(AsepriteLayer {
+            name,
+            visible: parent_visible && flags & LAYER_FLAG_VISIBLE != 0,
+            opacity: if self.layer_opacity { opacity } else { 255 },
+        });
+
+        if layer_type == LAYER_TYPE_GROUP {
+            self.groups.push(index);
+        }
+
+        Ok(())
+    }
+
+    fn parse_cel(&mut self, chunk_end: u64, previous_frames: &[Vec<Cel>]) -> io::Result<Option<Cel>> {
+        let layer = self.cursor.read_u16::<LittleEndian>()? as usize;
+        let x = i32::from(self.cursor.read_i16::<LittleEndian>()?);
+        let y = i32::from(self.cursor.read_i16::<LittleEndian>()?);
+        let opacity = self.cursor.read_u8()?;
+        let cel_type = self.cursor.read_u16::<LittleEndian>()?;
+
+        // Z-index and reserved bytes.
+        self.skip(7)?;
+
+        let image = match cel_type {
+            CEL_RAW => {
+                let (width, height) = self.read_cel_size()?;
+                let mut pixels = Vec::new();
+                self.read_until(chunk_end, &mut pixels)?;
+
+                self.decode_pixels(width, height, &pixels)?
+            }
+            CEL_COMPRESSED => {
+                let (width, height) = self.read_cel_size()?;
+                let mut compressed = Vec::new();
+                self.read_until(chunk_end, &mut compressed)?;
+
+                let pixels = inflate::inflate_bytes_zlib(&compressed).map_err(|e| invalid_data(&e))?;
+
+                self.decode_pixels(width, height, &pixels)?
+            }
+            CEL_LINKED => {
+                let frame = self.cursor.read_u16::<LittleEndian>()? as usize;
+                let linked = previous_frames
+                    .get(frame)
+                    .and_then(|cels| cels.iter().find(|cel| cel.layer == layer));
+
+                return Ok(linked.cloned());
+            }
+            // Tilemap cels aren't supported.
+            _ => return Ok(None),
+        };
+
+        Ok(Some(Cel {
+            layer,
+            x,
+            y,
+            opacity,
+            image,
+        }))
+    }
+
+    fn parse_tags(&mut self) -> io::Result<Vec<Tag>> {
+        let count = self.cursor.read_u16::<LittleEndian>()?;
+        self.skip(8)?;
+
+        let mut tags = Vec::new();
+
+        for _ in 0..count {
+            let from = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+            let to = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+            let direction = match self.cursor.read_u8()? {
+                1 => PlaybackDirection::Reverse,
+                2 | 3 => PlaybackDirection::PingPong,
+                _ => PlaybackDirection::Forward,
+            };
+
+            // Repeat count, reserved bytes, color and an extra byte.
+            self.skip(2 + 6 + 3 + 1)?;
+            let name = self.read_string()?;
+
+            tags.push((name, from, to, direction));
+        }
+
+        Ok(tags)
+    }
+
+    fn parse_palette(&mut self) -> io::Result<()> {
+        let size = self.cursor.read_u32::<LittleEndian>()? as usize;
+        let first = self.cursor.read_u32::<LittleEndian>()? as usize;
+        let last = self.cursor.read_u32::<LittleEndian>()? as usize;
+        self.skip(8)?;
+
+        if self.palette.len() < size {
+            self.palette.resize(size, Rgba([0, 0, 0, 0]));
+        }
+
+        for index in first..last + 1 {
+            let flags = self.cursor.read_u16::<LittleEndian>()?;
+            let mut color = [0; 4];
+            self.cursor.read_exact(&mut color)?;
+
+            if flags & 1 != 0 {
+                self.read_string()?;
+            }
+
+            if index < self.palette.len() {
+                self.palette[index] = Rgba(color);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn decode_pixels(&self, width: u32, height: u32, pixels: &[u8]) -> io::Result<RgbaImage> {
+        let bytes_per_pixel = self.color_depth as usize / 8;
+
+        if pixels.len() < width as usize * height as usize * bytes_per_pixel {
+            return Err(invalid_data("cel is missing pixel data"));
+        }
+
+        Ok(RgbaImage::from_fn(width, height, |x, y| {
+            let offset = (y as usize * width as usize + x as usize) * bytes_per_pixel;
+
+            match self.color_depth {
+                32 => Rgba([pixels[offset], pixels[offset + 1], pixels[offset + 2], pixels[offset + 3]]),
+                16 => Rgba([pixels[offset], pixels[offset], pixels[offset], pixels[offset + 1]]),
+                _ => {
+                    let index = pixels[offset];
+
+                    if index == self.transparent_index {
+                        Rgba([0, 0, 0, 0])
+                    } else {
+                        self.palette.get(index as usize).cloned().unwrap_or(Rgba([0, 0, 0, 0]))
+                    }
+                }
+            }
+        }))
+    }
+
+    /// Draws the cels of the visible layers on top of each other.
+    fn flatten(&self, cels: &[Cel]) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+
+        for cel in cels {
+            let layer = match self.layers.get(cel.layer) {
+                Some(layer) if layer.visible => layer,
+                _ => continue,
+            };
+
+            let opacity = f32::from(cel.opacity) / 255.0 * f32::from(layer.opacity) / 255.0;
+
+            for (x, y, pixel) in cel.image.enumerate_pixels() {
+                let (target_x, target_y) = (cel.x + x as i32, cel.y + y as i32);
+
+                if target_x < 0 || target_y < 0 || target_x >= self.width as i32 || target_y >= self.height as i32 {
+                    continue;
+                }
+
+                let below = *image.get_pixel(target_x as u32, target_y as u32);
+                image.put_pixel(target_x as u32, target_y as u32, blend(below, *pixel, opacity));
+            }
+        }
+
+        image
+    }
+
+    fn read_cel_size(&mut self) -> io::Result<(u32, u32)> {
+        let width = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+        let height = u32::from(self.cursor.read_u16::<LittleEndian>()?);
+
+        Ok((width, height))
+    }
+
+    fn read_until(&mut self, end: u64, buffer: &mut Vec<u8>) -> io::Result<()> {
+        let length = end.saturating_sub(self.cursor.position());
+        (&mut self.cursor).take(length).read_to_end(buffer)?;
+
+        Ok(())
+    }
+
+    fn read_string(&mut self) -> io::Result<String> {
+        let length = self.cursor.read_u16::<LittleEndian>()? as usize;
+        let mut bytes = vec![0; length];
+        self.cursor.read_exact(&mut bytes)?;
+
+        String::from_utf8(bytes).map_err(|_| invalid_data("string is not UTF-8"))
+    }
+
+    fn skip(&mut self, bytes: i64) -> io::Result<()> {
+        self.cursor.seek(SeekFrom::Current(bytes))?;
+        Ok(())
+    }
+}
+
+/// Normal blending of `above` over `below`, with straight alpha.
+fn blend(below: Rgba<u8>, above: Rgba<u8>, opacity: f32) -> Rgba<u8> {
+    let above_alpha = f32::from(above.data[3]) / 255.0 * opacity;
+    let below_alpha = f32::from(below.data[3]) / 255.0;
+    let alpha = above_alpha + below_alpha * (1.0 - above_alpha);
+
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let mut result = [0; 4];
+
+    for channel in 0..3 {
+        let color = (f32::from(above.data[channel]) * above_alpha
+            + f32::from(below.data[channel]) * below_alpha * (1.0 - above_alpha)) / alpha;
+        result[channel] = color.round().min(255.0) as u8;
+    }
+
+    result[3] = (alpha * 255.0).round().min(255.0) as u8;
+
+    Rgba(result)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    fn write_string(bytes: &mut Vec<u8>, string: &str) {
+        bytes.write_u16::<LittleEndian>(string.len() as u16).unwrap();
+        bytes.extend(string.as_bytes());
+    }
+
+    /// A whole file: the 128 byte header followed by the frames.
+    fn file(width: u16, height: u16, color_depth: u16, frames: Vec<Vec<u8>>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.write_u16::<LittleEndian>(HEADER_MAGIC).unwrap();
+        bytes.write_u16::<LittleEndian>(frames.len() as u16).unwrap();
+        bytes.write_u16::<LittleEndian>(width).unwrap();
+        bytes.write_u16::<LittleEndian>(height).unwrap();
+        bytes.write_u16::<LittleEndian>(color_depth).unwrap();
+        bytes.write_u32::<LittleEndian>(0).unwrap();
+        bytes.resize(HEADER_SIZE as usize, 0);
+
+        for frame in frames {
+            bytes.extend(frame);
+        }
+
+        let size = bytes.len() as u32;
+        (&mut bytes[0..4]).write_u32::<LittleEndian>(size).unwrap();
+
+        bytes
+    }
+
+    fn frame(duration: u16, chunks: Vec<Vec<u8>>) -> Vec<u8> {
+        let body: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.iter().cloned()).collect();
+
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(16 + body.len() as u32).unwrap();
+        bytes.write_u16::<LittleEndian>(FRAME_MAGIC).unwrap();
+        bytes.write_u16::<LittleEndian>(chunks.len() as u16).unwrap();
+        bytes.write_u16::<LittleEndian>(duration).unwrap();
+        bytes.extend(&[0; 2]);
+        bytes.write_u32::<LittleEndian>(chunks.len() as u32).unwrap();
+        bytes.extend(body);
+
+        bytes
+    }
+
+    fn chunk(chunk_type: u16, body: Vec<u8>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.write_u32::<LittleEndian>(6 + body.len() as u32).unwrap();
+        bytes.write_u16::<LittleEndian>(chunk_type).unwrap();
+        bytes.extend(body);
+
+        bytes
+    }
+
+    fn layer(name: &str, visible: bool, group: bool, child_level: u16) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.write_u16::<LittleEndian>(if visible { LAYER_FLAG_VISIBLE } else { 0 }).unwrap();
+        body.write_u16::<LittleEndian>(if group { LAYER_TYPE_GROUP } else { 0 }).unwrap();
+        body.write_u16::<LittleEndian>(child_level).unwrap();
+        body.extend(&[0; 6]);
+        body.push(255);
+        body.extend(&[0; 3]);
+        write_string(&mut body, name);
+
+        chunk(CHUNK_LAYER, body)
+    }
+
+    fn cel_header(layer: u16, x: i16, y: i16, cel_type: u16) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.write_u16::<LittleEndian>(layer).unwrap();
+        body.write_i16::<LittleEndian>(x).unwrap();
+        body.write_i16::<LittleEndian>(y).unwrap();
+        body.push(255);
+        body.write_u16::<LittleEndian>(cel_type).unwrap();
+        body.extend(&[0; 7]);
+
+        body
+    }
+
+    /// A one pixel cel in a single color.
+    fn pixel_cel(layer: u16, x: i16, y: i16, color: [u8; 4]) -> Vec<u8> {
+        let mut body = cel_header(layer, x, y, CEL_RAW);
+        body.write_u16::<LittleEndian>(1).unwrap();
+        body.write_u16::<LittleEndian>(1).unwrap();
+        body.extend(&color);
+
+        chunk(CHUNK_CEL, body)
+    }
+
+    fn linked_cel(layer: u16, frame: u16) -> Vec<u8> {
+        let mut body = cel_header(layer, 0, 0, CEL_LINKED);
+        body.write_u16::<LittleEndian>(frame).unwrap();
+
+        chunk(CHUNK_CEL, body)
+    }
+
+    fn tags(tags: &[(&str, u16, u16, u8)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.write_u16::<LittleEndian>(tags.len() as u16).unwrap();
+        body.extend(&[0; 8]);
+
+        for &(name, from, to, direction) in tags {
+            body.write_u16::<LittleEndian>(from).unwrap();
+            body.write_u16::<LittleEndian>(to).unwrap();
+            body.push(direction);
+            body.extend(&[0; 12]);
+            write_string(&mut body, name);
+        }
+
+        chunk(CHUNK_TAGS, body)
+    }
+
+    fn clip_frames(aseprite: &Aseprite, name: &str) -> Vec<u32> {
+        aseprite.clip(name).unwrap().frames.iter().map(|frame| frame.frame).collect()
+    }
+
+    #[test]
+    fn rejects_files_that_arent_aseprite() {
+        let mut bytes = file(1, 1, 32, vec![frame(100, Vec::new())]);
+        bytes[4] = 0x89;
+
+        let error = Aseprite::parse(&bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_unsupported_color_depths() {
+        let bytes = file(1, 1, 24, vec![frame(100, Vec::new())]);
+
+        let error = Aseprite::parse(&bytes).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn linked_cels_repeat_an_earlier_frame() {
+        let bytes = file(
+            2,
+            1,
+            32,
+            vec![
+                frame(100, vec![layer("body", true, false, 0), pixel_cel(0, 1, 0, RED)]),
+                frame(200, vec![linked_cel(0, 0)]),
+            ],
+        );
+
+        let aseprite = Aseprite::parse(&bytes).unwrap();
+
+        assert_eq!(aseprite.frames.len(), 2);
+        assert_eq!(aseprite.frames[1].duration, Duration::from_millis(200));
+        assert_eq!(*aseprite.frames[1].image.get_pixel(1, 0), Rgba(RED));
+        assert_eq!(*aseprite.frames[1].image.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn hidden_groups_hide_their_children() {
+        let bytes = file(
+            2,
+            1,
+            32,
+            vec![frame(
+                100,
+                vec![
+                    layer("hidden", false, true, 0),
+                    layer("child", true, false, 1),
+                    layer("shown", true, false, 0),
+                    pixel_cel(1, 0, 0, RED),
+                    pixel_cel(2, 1, 0, BLUE),
+                ],
+            )],
+        );
+
+        let aseprite = Aseprite::parse(&bytes).unwrap();
+        let visible: Vec<bool> = aseprite.layers.iter().map(|layer| layer.visible).collect();
+
+        assert_eq!(visible, vec![false, false, true]);
+        assert_eq!(*aseprite.frames[0].image.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+        assert_eq!(*aseprite.frames[0].image.get_pixel(1, 0), Rgba(BLUE));
+    }
+
+    #[test]
+    fn tags_become_clips_in_their_direction() {
+        let bytes = file(
+            1,
+            1,
+            32,
+            vec![
+                frame(
+                    100,
+                    vec![tags(&[("walk", 0, 2, 0), ("back", 0, 2, 1), ("bounce", 0, 2, 2)])],
+                ),
+                frame(100, Vec::new()),
+                frame(100, Vec::new()),
+            ],
+        );
+
+        let aseprite = Aseprite::parse(&bytes).unwrap();
+
+        assert_eq!(clip_frames(&aseprite, "walk"), vec![0, 1, 2]);
+        assert_eq!(clip_frames(&aseprite, "back"), vec![2, 1, 0]);
+        assert_eq!(clip_frames(&aseprite, "bounce"), vec![0, 1, 2, 1]);
+        assert!(aseprite.clip("run").is_none());
+    }
+
+    #[test]
+    fn blends_with_straight_alpha() {
+        let black = Rgba([0, 0, 0, 255]);
+        let white = Rgba([255, 255, 255, 255]);
+
+        assert_eq!(blend(black, white, 1.0), white);
+        assert_eq!(blend(black, white, 0.5), Rgba([128, 128, 128, 255]));
+        assert_eq!(blend(Rgba([0, 0, 0, 0]), Rgba([255, 0, 0, 128]), 1.0), Rgba([255, 0, 0, 128]));
+        assert_eq!(blend(Rgba([0, 0, 0, 0]), white, 0.0), Rgba([0, 0, 0, 0]));
+    }
+}
@@ -1,17 +1,132 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
 use std::path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
+use ggez::audio;
 use ggez::graphics;
-use ggez::graphics::{Font, Scale};
+use ggez::graphics::{FilterMode, Font, Scale};
 use ggez::{Context, GameError, GameResult};
 
+use image::{self, Rgba, RgbaImage};
+use serde::de::DeserializeOwned;
+use toml;
 use warmy;
 
+use animated_gif::AnimatedGif;
 use screen::Screen;
+use species::SpeciesRegistry;
+use tilemap::TileSetRegistry;
+
+/// Why an asset couldn't be loaded.
+#[derive(Debug, Clone)]
+pub enum AssetError {
+    MissingFile(path::PathBuf),
+    /// The file was read but its contents aren't what we expected.
+    Decode { path: path::PathBuf, reason: String },
+    /// warmy handed us a path outside of the resources directory.
+    PathEscape(path::PathBuf),
+    Store(String),
+}
+
+impl fmt::Display for AssetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AssetError::MissingFile(ref path) => write!(f, "{} does not exist", path.display()),
+            AssetError::Decode { ref path, ref reason } => write!(f, "Could not decode {}: {}", path.display(), reason),
+            AssetError::PathEscape(ref path) => write!(f, "{} is outside of the resources directory", path.display()),
+            AssetError::Store(ref reason) => write!(f, "Asset store error: {}", reason),
+        }
+    }
+}
+
+impl Error for AssetError {
+    fn description(&self) -> &str {
+        match *self {
+            AssetError::MissingFile(_) => "missing file",
+            AssetError::Decode { .. } => "decode failure",
+            AssetError::PathEscape(_) => "path outside of the resources directory",
+            AssetError::Store(_) => "asset store error",
+        }
+    }
+}
+
+impl From<AssetError> for GameError {
+    fn from(e: AssetError) -> GameError {
+        GameError::ResourceLoadError(e.to_string())
+    }
+}
+
+impl AssetError {
+    pub fn decode<E: fmt::Display>(path: &path::Path, reason: E) -> AssetError {
+        AssetError::Decode {
+            path: path.to_owned(),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Fetches a resource from the store, keeping the loader's `AssetError`.
+pub fn get<T>(asset_store: &mut warmy::Store<Context>, path: &str, ctx: &mut Context) -> Result<warmy::Res<T>, AssetError>
+where
+    T: warmy::Load<Context, Key = warmy::FSKey, Error = AssetError>,
+{
+    asset_store
+        .get::<_, T>(&warmy::FSKey::new(path), ctx)
+        .map_err(|e| match e {
+            warmy::load::StoreErrorOr::ResError(e) => e,
+            warmy::load::StoreErrorOr::StoreError(e) => AssetError::Store(e.to_string()),
+        })
+}
+
+/// Magenta and black checkers, drawn in place of images that failed to load
+/// so the problem is obvious without taking the game down.
+pub fn missing_texture_pixels(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        if (x / 4 + y / 4) % 2 == 0 {
+            Rgba([255, 0, 255, 255])
+        } else {
+            Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+pub fn missing_texture(ctx: &mut Context) -> GameResult<graphics::Image> {
+    let pixels = missing_texture_pixels(32, 32);
+    let mut image = graphics::Image::from_rgba8(ctx, 32, 32, &pixels)?;
+    image.set_filter(FilterMode::Nearest);
+
+    Ok(image)
+}
+
+static GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+/// Returns a different number every time it's called. Assets are stamped
+/// with one when loaded, so whoever holds a `warmy::Res` can tell that warmy
+/// reloaded it since they last looked.
+pub fn next_generation() -> usize {
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Height of UI text in logical pixels, before the user's text scale.
+pub const UI_TEXT_PIXELS: f32 = 24.0;
 
 pub struct Assets {
     pub font: Font,
     pub default_scale: Scale,
     pub asset_store: warmy::Store<Context>,
+    /// Empty until the `Preloader` gets to it.
+    pub tilesets: TileSetRegistry,
+    pub species: SpeciesRegistry,
+    pub images: HashMap<String, warmy::Res<Image>>,
+    pub animations: HashMap<String, warmy::Res<AnimatedGif>>,
+    pub fonts: HashMap<String, Font>,
+    pub sounds: HashMap<String, warmy::Res<Sound>>,
+    /// What went wrong loading or reloading assets, until a scene takes it
+    /// to show the player. The game has no console to print it to.
+    pub errors: Vec<String>,
 }
 
 impl Assets {
@@ -19,6 +134,7 @@ impl Assets {
         resource_dir: Option<path::PathBuf>,
         ctx: &mut Context,
         screen: &Screen,
+        ui_scale: f32,
     ) -> GameResult<Assets> {
         let resource_pathbuf: path::PathBuf = match resource_dir {
             Some(s) => s,
@@ -27,15 +143,62 @@ impl Assets {
 
         let font = Font::new_glyph_font(ctx, "/fonts/m5x7.ttf")?;
         let opt = warmy::StoreOpt::default().set_root(resource_pathbuf);
-        let asset_store = warmy::Store::new(opt).expect("No asset store?");
+        let asset_store = warmy::Store::new(opt).map_err(|e| AssetError::Store(e.to_string()))?;
 
         Ok(Assets {
             font: font,
-            default_scale: Assets::display_independent_scale(screen.scale_w, screen.scale_h, 24.0),
+            default_scale: Assets::display_independent_scale(screen.scale_w, screen.scale_h, UI_TEXT_PIXELS * ui_scale),
             asset_store,
+            tilesets: TileSetRegistry::empty(),
+            species: SpeciesRegistry::empty(),
+            images: HashMap::new(),
+            animations: HashMap::new(),
+            fonts: HashMap::new(),
+            sounds: HashMap::new(),
+            errors: Vec::new(),
         })
     }
 
+    /// Picks up changed files from disk and rebuilds whatever depends on them.
+    pub fn sync(&mut self, ctx: &mut Context) {
+        self.asset_store.sync(ctx);
+
+        if let Err(e) = self.tilesets.sync(&mut self.asset_store, ctx) {
+            self.errors.push(format!("Could not reload tilesets: {}", e));
+        }
+
+        self.errors.extend(self.tilesets.errors.drain(..));
+    }
+
+    /// A preloaded image by its name in the manifest, or the missing texture
+    /// if it failed to load.
+    pub fn image(&self, name: &str, ctx: &mut Context) -> GameResult<graphics::Image> {
+        match self.images.get(name) {
+            Some(image) => Ok(image.borrow().0.clone()),
+            None => missing_texture(ctx),
+        }
+    }
+
+    pub fn animation(&self, name: &str, ctx: &mut Context) -> GameResult<AnimatedGif> {
+        match self.animations.get(name) {
+            Some(animation) => Ok(animation.borrow().clone()),
+            None => AnimatedGif::placeholder(ctx),
+        }
+    }
+
+    /// A preloaded font by name, falling back to the UI font.
+    pub fn font(&self, name: &str) -> Font {
+        self.fonts.get(name).cloned().unwrap_or_else(|| self.font.clone())
+    }
+
+    pub fn sound(&self, name: &str) -> Option<audio::SoundData> {
+        self.sounds.get(name).map(|sound| sound.borrow().0.clone())
+    }
+
+    pub fn resize(&mut self, screen: &Screen, ui_scale: f32) {
+        self.default_scale = Assets::display_independent_scale(screen.scale_w, screen.scale_h, UI_TEXT_PIXELS * ui_scale);
+    }
+
     pub fn display_independent_scale(scale_w: f32, scale_h: f32, pixels: f32) -> Scale {
         Scale {
             x: pixels * scale_h,
@@ -44,32 +207,110 @@ impl Assets {
     }
 }
 
-fn warmy_to_ggez_path(path: &path::Path, root: &path::Path) -> path::PathBuf {
+fn warmy_to_ggez_path(path: &path::Path, root: &path::Path) -> Result<path::PathBuf, AssetError> {
     let stripped_path = path.strip_prefix(root)
-        .expect("warmy path is outside of the warmy store somehow");
+        .map_err(|_| AssetError::PathEscape(path.to_owned()))?;
+
+    Ok(path::Path::new("/").join(stripped_path))
+}
+
+pub fn read_bytes(key: &warmy::FSKey, root: &path::Path, ctx: &mut Context) -> Result<Vec<u8>, AssetError> {
+    let path = warmy_to_ggez_path(key.as_path(), root)?;
+
+    if !ctx.filesystem.is_file(&path) {
+        return Err(AssetError::MissingFile(path));
+    }
+
+    let mut file = ctx.filesystem
+        .open(&path)
+        .map_err(|_| AssetError::MissingFile(path.clone()))?;
+    let mut bytes = Vec::new();
+
+    file.read_to_end(&mut bytes)
+        .map_err(|e| AssetError::decode(&path, e))?;
 
-    path::Path::new("/").join(stripped_path)
+    Ok(bytes)
+}
+
+/// Reads a TOML file from the resources directory and deserializes it.
+pub fn read_toml<T>(key: &warmy::FSKey, root: &path::Path, ctx: &mut Context) -> Result<T, AssetError>
+where
+    T: DeserializeOwned,
+{
+    let bytes = read_bytes(key, root, ctx)?;
+    let contents = String::from_utf8(bytes)
+        .map_err(|e| AssetError::decode(key.as_path(), e))?;
+
+    toml::from_str(&contents).map_err(|e| AssetError::decode(key.as_path(), e))
 }
 
 #[derive(Debug, Clone)]
 pub struct Image(pub graphics::Image);
 impl warmy::Load<Context> for Image {
     type Key = warmy::FSKey;
-    type Error = GameError;
+    type Error = AssetError;
 
     fn load(
         key: Self::Key,
         store: &mut warmy::Storage<Context>,
         ctx: &mut Context,
     ) -> Result<warmy::Loaded<Self>, Self::Error> {
-        let path = warmy_to_ggez_path(key.as_path(), store.root());
+        let path = warmy_to_ggez_path(key.as_path(), store.root())?;
 
-        graphics::Image::new(ctx, path)
+        if !ctx.filesystem.is_file(&path) {
+            return Err(AssetError::MissingFile(path));
+        }
+
+        graphics::Image::new(ctx, &path)
             .map(|x| warmy::Loaded::from(Image(x)))
-            .map_err(|_| {
-                GameError::ResourceLoadError(
-                    format!("Could not load {:?}", key.as_path()).to_string(),
-                )
+            .map_err(|e| AssetError::decode(&path, e))
+    }
+}
+
+/// Decoded pixels of an image, for building textures such as the sprite atlas
+/// instead of uploading the file as is.
+#[derive(Debug, Clone)]
+pub struct Pixels {
+    pub image: RgbaImage,
+    pub generation: usize,
+}
+
+impl warmy::Load<Context> for Pixels {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let bytes = read_bytes(&key, store.root(), ctx)?;
+
+        image::load_from_memory(&bytes)
+            .map(|x| {
+                warmy::Loaded::from(Pixels {
+                    image: x.to_rgba(),
+                    generation: next_generation(),
+                })
             })
+            .map_err(|e| AssetError::decode(key.as_path(), e))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Sound(pub audio::SoundData);
+
+impl warmy::Load<Context> for Sound {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let bytes = read_bytes(&key, store.root(), ctx)?;
+
+        Ok(warmy::Loaded::from(Sound(audio::SoundData::from_bytes(&bytes))))
     }
 }
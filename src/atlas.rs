@@ -0,0 +1,198 @@
+use std::collections::HashMap;
+
+use ggez::graphics::{self, FilterMode, Rect};
+use ggez::{Context, GameError, GameResult};
+use image::RgbaImage;
+
+/// Width and height of each atlas page.
+pub const ATLAS_SIZE: u32 = 1024;
+
+/// Transparent gap between regions so neighbouring frames never bleed into
+/// each other.
+const PADDING: u32 = 1;
+
+#[derive(Debug, Copy, Clone)]
+pub struct AtlasRegion {
+    pub page: usize,
+    /// Source rectangle as a fraction of the page, ready for `DrawParam::src`.
+    pub src: Rect,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub struct Atlas {
+    pub pages: Vec<graphics::Image>,
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn empty() -> Atlas {
+        Atlas {
+            pages: Vec::new(),
+            regions: HashMap::new(),
+        }
+    }
+
+    pub fn region(&self, name: &str) -> Option<&AtlasRegion> {
+        self.regions.get(name)
+    }
+}
+
+/// Packs images into as few `ATLAS_SIZE` pages as possible using rows
+/// ("shelves") of images sorted by height.
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        AtlasBuilder {
+            images: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: &str, image: RgbaImage) {
+        self.images.push((name.to_string(), image));
+    }
+
+    pub fn build(mut self, ctx: &mut Context) -> GameResult<Atlas> {
+        self.images.sort_by(|(_, a), (_, b)| b.height().cmp(&a.height()));
+
+        let placements = pack(&self.images)?;
+        let page_count = placements.iter().map(|placement| placement.page + 1).max().unwrap_or(1);
+
+        let mut pages = vec![RgbaImage::new(ATLAS_SIZE, ATLAS_SIZE); page_count];
+        let mut regions = HashMap::new();
+
+        for ((name, image), placement) in self.images.iter().zip(placements) {
+            let (width, height) = image.dimensions();
+
+            for (pixel_x, pixel_y, pixel) in image.enumerate_pixels() {
+                pages[placement.page].put_pixel(placement.x + pixel_x, placement.y + pixel_y, *pixel);
+            }
+
+            regions.insert(
+                name.clone(),
+                AtlasRegion {
+                    page: placement.page,
+                    src: Rect::new(
+                        placement.x as f32 / ATLAS_SIZE as f32,
+                        placement.y as f32 / ATLAS_SIZE as f32,
+                        width as f32 / ATLAS_SIZE as f32,
+                        height as f32 / ATLAS_SIZE as f32,
+                    ),
+                    width,
+                    height,
+                },
+            );
+        }
+
+        let mut images = Vec::with_capacity(pages.len());
+
+        for page in pages.iter() {
+            let mut image = graphics::Image::from_rgba8(ctx, ATLAS_SIZE as u16, ATLAS_SIZE as u16, page)?;
+            image.set_filter(FilterMode::Nearest);
+            images.push(image);
+        }
+
+        Ok(Atlas {
+            pages: images,
+            regions,
+        })
+    }
+}
+
+/// Where an image goes: its page and the pixel position of its top left
+/// corner on that page.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Placement {
+    page: usize,
+    x: u32,
+    y: u32,
+}
+
+/// Lays the images out in order on shelves, starting a new shelf when one
+/// fills up and a new page when the shelves do.
+fn pack(images: &[(String, RgbaImage)]) -> GameResult<Vec<Placement>> {
+    let mut placements = Vec::with_capacity(images.len());
+    let (mut page, mut x, mut y, mut shelf_height) = (0, 0, 0, 0);
+
+    for (name, image) in images.iter() {
+        let (width, height) = image.dimensions();
+
+        if width > ATLAS_SIZE || height > ATLAS_SIZE {
+            return Err(GameError::ResourceLoadError(format!(
+                "{} is {}x{}, larger than an atlas page",
+                name, width, height
+            )));
+        }
+
+        if x + width > ATLAS_SIZE {
+            x = 0;
+            y += shelf_height + PADDING;
+            shelf_height = 0;
+        }
+
+        if y + height > ATLAS_SIZE {
+            page += 1;
+            x = 0;
+            y = 0;
+            shelf_height = 0;
+        }
+
+        placements.push(Placement { page, x, y });
+
+        x += width + PADDING;
+        shelf_height = shelf_height.max(height);
+    }
+
+    Ok(placements)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn images(sizes: &[(u32, u32)]) -> Vec<(String, RgbaImage)> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(index, &(width, height))| (index.to_string(), RgbaImage::new(width, height)))
+            .collect()
+    }
+
+    #[test]
+    fn wraps_to_a_new_shelf_when_a_row_is_full() {
+        let placements = pack(&images(&[(600, 100), (300, 50), (200, 80)])).unwrap();
+
+        assert_eq!(
+            placements,
+            vec![
+                Placement { page: 0, x: 0, y: 0 },
+                Placement { page: 0, x: 601, y: 0 },
+                Placement { page: 0, x: 0, y: 101 },
+            ]
+        );
+    }
+
+    #[test]
+    fn opens_a_new_page_when_the_shelves_run_out() {
+        let placements = pack(&images(&[(1024, 600), (1024, 400), (1024, 100)])).unwrap();
+
+        assert_eq!(
+            placements,
+            vec![
+                Placement { page: 0, x: 0, y: 0 },
+                Placement { page: 0, x: 0, y: 601 },
+                Placement { page: 1, x: 0, y: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_images_larger_than_a_page() {
+        assert!(pack(&images(&[(16, 16), (ATLAS_SIZE + 1, 16)])).is_err());
+        assert!(pack(&images(&[(16, ATLAS_SIZE + 1)])).is_err());
+        assert!(pack(&images(&[(ATLAS_SIZE, ATLAS_SIZE)])).is_ok());
+    }
+}
@@ -0,0 +1,322 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+use ggez::audio::Source;
+use ggez::{Context, GameResult};
+
+use assets::Assets;
+use settings::Settings;
+
+/// Seconds it takes music to fade in or out when the track changes.
+const CROSSFADE_SECONDS: f32 = 1.5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SoundEffect {
+    Step,
+    Bump,
+    Plant,
+    Grow,
+    MenuMove,
+    MenuSelect,
+}
+
+impl SoundEffect {
+    /// Name of the sound in the asset manifest.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            SoundEffect::Step => "step",
+            SoundEffect::Bump => "bump",
+            SoundEffect::Plant => "plant",
+            SoundEffect::Grow => "grow",
+            SoundEffect::MenuMove => "menu-move",
+            SoundEffect::MenuSelect => "menu-select",
+        }
+    }
+}
+
+pub type LoopId = usize;
+
+/// Whatever actually makes the noise. Calls only record what should happen;
+/// `update` is where a backend gets the `Context` to do it.
+pub trait AudioBackend {
+    fn play(&mut self, sound: &str, volume: f32);
+
+    fn start_loop(&mut self, id: LoopId, sound: &str, volume: f32);
+
+    fn set_loop_volume(&mut self, id: LoopId, volume: f32);
+
+    fn stop_loop(&mut self, id: LoopId);
+
+    fn update(&mut self, _ctx: &mut Context, _assets: &Assets) -> GameResult<()> {
+        Ok(())
+    }
+}
+
+/// Plays nothing, so `Audio` can be driven without a window or sound device.
+/// It keeps the name of every sound it was asked to play, for tests to check.
+#[derive(Default)]
+pub struct NullBackend {
+    played: Rc<RefCell<Vec<String>>>,
+}
+
+impl NullBackend {
+    pub fn new() -> NullBackend {
+        NullBackend::default()
+    }
+
+    /// The sounds played so far. The list is shared, so it can still be read
+    /// once the backend has been handed to `Audio`.
+    pub fn played(&self) -> Rc<RefCell<Vec<String>>> {
+        self.played.clone()
+    }
+}
+
+impl AudioBackend for NullBackend {
+    fn play(&mut self, sound: &str, _volume: f32) {
+        self.played.borrow_mut().push(sound.to_string());
+    }
+
+    fn start_loop(&mut self, _id: LoopId, _sound: &str, _volume: f32) {}
+
+    fn set_loop_volume(&mut self, _id: LoopId, _volume: f32) {}
+
+    fn stop_loop(&mut self, _id: LoopId) {}
+}
+
+enum Command {
+    Play(String, f32),
+    StartLoop(LoopId, String, f32),
+    SetLoopVolume(LoopId, f32),
+    StopLoop(LoopId),
+}
+
+/// Plays sounds from the preloaded assets through ggez.
+pub struct GgezBackend {
+    commands: Vec<Command>,
+    /// One-shot sounds stop when their `Source` is dropped, so they're kept
+    /// until they finish.
+    playing: Vec<Source>,
+    loops: HashMap<LoopId, Source>,
+}
+
+impl GgezBackend {
+    pub fn new() -> GgezBackend {
+        GgezBackend {
+            commands: Vec::new(),
+            playing: Vec::new(),
+            loops: HashMap::new(),
+        }
+    }
+}
+
+impl AudioBackend for GgezBackend {
+    fn play(&mut self, sound: &str, volume: f32) {
+        self.commands.push(Command::Play(sound.to_string(), volume));
+    }
+
+    fn start_loop(&mut self, id: LoopId, sound: &str, volume: f32) {
+        self.commands.push(Command::StartLoop(id, sound.to_string(), volume));
+    }
+
+    fn set_loop_volume(&mut self, id: LoopId, volume: f32) {
+        self.commands.push(Command::SetLoopVolume(id, volume));
+    }
+
+    fn stop_loop(&mut self, id: LoopId) {
+        self.commands.push(Command::StopLoop(id));
+    }
+
+    fn update(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        self.playing.retain(|source| source.playing());
+
+        for command in self.commands.drain(..) {
+            match command {
+                Command::Play(sound, volume) => {
+                    // Missing sounds were already reported by the preloader.
+                    if let Some(data) = assets.sound(&sound) {
+                        let mut source = Source::from_data(ctx, data)?;
+                        source.set_volume(volume);
+                        source.play()?;
+                        self.playing.push(source);
+                    }
+                }
+                Command::StartLoop(id, sound, volume) => {
+                    if let Some(data) = assets.sound(&sound) {
+                        let mut source = Source::from_data(ctx, data)?;
+                        source.set_repeat(true);
+                        source.set_volume(volume);
+                        source.play()?;
+                        self.loops.insert(id, source);
+                    }
+                }
+                Command::SetLoopVolume(id, volume) => {
+                    if let Some(source) = self.loops.get_mut(&id) {
+                        source.set_volume(volume);
+                    }
+                }
+                Command::StopLoop(id) => {
+                    if let Some(source) = self.loops.remove(&id) {
+                        source.stop();
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct MusicTrack {
+    id: LoopId,
+    name: String,
+    /// How far faded in the track is, from 0 to 1.
+    fade: f32,
+    fading_in: bool,
+}
+
+/// Sound effects and music, with separate volumes for each. Changing the
+/// music fades the old track out while the new one fades in.
+pub struct Audio {
+    backend: Box<AudioBackend>,
+    music_volume: f32,
+    sfx_volume: f32,
+    tracks: Vec<MusicTrack>,
+    next_loop_id: LoopId,
+}
+
+impl Audio {
+    pub fn new(backend: Box<AudioBackend>, settings: &Settings) -> Audio {
+        Audio {
+            backend,
+            music_volume: settings.music_volume,
+            sfx_volume: settings.sfx_volume,
+            tracks: Vec::new(),
+            next_loop_id: 0,
+        }
+    }
+
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.music_volume = settings.music_volume;
+        self.sfx_volume = settings.sfx_volume;
+
+        for track in self.tracks.iter() {
+            self.backend.set_loop_volume(track.id, track.fade * self.music_volume);
+        }
+    }
+
+    pub fn play(&mut self, effect: SoundEffect) {
+        if self.sfx_volume > 0.0 {
+            self.backend.play(effect.name(), self.sfx_volume);
+        }
+    }
+
+    /// Crossfades to the named track, unless it's already the one playing.
+    pub fn play_music(&mut self, name: &str) {
+        if self.current_music() == Some(name) {
+            return;
+        }
+
+        for track in self.tracks.iter_mut() {
+            track.fading_in = track.name == name;
+        }
+
+        if !self.tracks.iter().any(|track| track.name == name) {
+            let id = self.next_loop_id;
+            self.next_loop_id += 1;
+
+            self.backend.start_loop(id, name, 0.0);
+            self.tracks.push(MusicTrack {
+                id,
+                name: name.to_string(),
+                fade: 0.0,
+                fading_in: true,
+            });
+        }
+    }
+
+    pub fn stop_music(&mut self) {
+        for track in self.tracks.iter_mut() {
+            track.fading_in = false;
+        }
+    }
+
+    /// The track that is playing or fading in.
+    pub fn current_music(&self) -> Option<&str> {
+        self.tracks
+            .iter()
+            .find(|track| track.fading_in)
+            .map(|track| track.name.as_str())
+    }
+
+    /// Advances the crossfade, stopping tracks that have faded out.
+    pub fn step(&mut self, dt: Duration) {
+        let seconds = dt.as_secs() as f32 + dt.subsec_nanos() as f32 / 1_000_000_000.0;
+        let change = seconds / CROSSFADE_SECONDS;
+
+        for track in self.tracks.iter_mut() {
+            let fade = if track.fading_in {
+                (track.fade + change).min(1.0)
+            } else {
+                (track.fade - change).max(0.0)
+            };
+
+            if fade != track.fade {
+                track.fade = fade;
+                self.backend.set_loop_volume(track.id, fade * self.music_volume);
+            }
+        }
+
+        let backend = &mut self.backend;
+
+        self.tracks.retain(|track| {
+            let finished = !track.fading_in && track.fade <= 0.0;
+
+            if finished {
+                backend.stop_loop(track.id);
+            }
+
+            !finished
+        });
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, assets: &Assets) -> GameResult<()> {
+        self.backend.update(ctx, assets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(settings: &Settings) -> (Audio, Rc<RefCell<Vec<String>>>) {
+        let backend = NullBackend::new();
+        let played = backend.played();
+
+        (Audio::new(Box::new(backend), settings), played)
+    }
+
+    #[test]
+    fn plays_effects_by_name() {
+        let (mut audio, played) = audio(&Settings::default());
+
+        audio.play(SoundEffect::Step);
+        audio.play(SoundEffect::Bump);
+
+        assert_eq!(*played.borrow(), vec!["step".to_string(), "bump".to_string()]);
+    }
+
+    #[test]
+    fn muted_effects_are_not_played() {
+        let settings = Settings {
+            sfx_volume: 0.0,
+            ..Settings::default()
+        };
+        let (mut audio, played) = audio(&settings);
+
+        audio.play(SoundEffect::Bump);
+
+        assert!(played.borrow().is_empty());
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+
+use specs::Entity;
+
+use environment::Environment;
+
+/// What stands on a tile, as far as moving onto it is concerned. When
+/// several entities share a tile, the one that matters most wins: blocks,
+/// then anything else `Solid`, then movers, then the rest.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Occupant {
+    /// Can be pushed; `chain` is whether it shoves a block beyond it along.
+    Pushable { entity: Entity, chain: bool },
+    Solid,
+    /// The player or a creature. Only one of them fits on a tile, so they
+    /// bump into each other rather than share it.
+    Mover,
+    /// Something that can be walked over but not pushed onto, like a plant.
+    Other,
+}
+
+impl Occupant {
+    fn priority(&self) -> u32 {
+        match *self {
+            Occupant::Pushable { .. } => 3,
+            Occupant::Solid => 2,
+            Occupant::Mover => 1,
+            Occupant::Other => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Occupancy {
+    pub tiles: HashMap<(i32, i32), Occupant>,
+}
+
+impl Occupancy {
+    pub fn add(&mut self, position: (i32, i32), occupant: Occupant) {
+        let replace = match self.tiles.get(&position) {
+            Some(existing) => occupant.priority() > existing.priority(),
+            None => true,
+        };
+
+        if replace {
+            self.tiles.insert(position, occupant);
+        }
+    }
+
+    pub fn get(&self, position: (i32, i32)) -> Option<&Occupant> {
+        self.tiles.get(&position)
+    }
+}
+
+/// Works out what happens when something at the tile before `target` moves
+/// onto it in `direction`. Returns the blocks that get pushed along, nearest
+/// first, or `None` if the move is blocked. Only walkable tiles of the
+/// level can be stepped on, and pushed blocks need one with nothing on it.
+pub fn push_chain(
+    target: (i32, i32),
+    direction: (i32, i32),
+    occupancy: &Occupancy,
+    environment: &Environment,
+) -> Option<Vec<Entity>> {
+    match environment.get(target) {
+        Some(soil) if soil.is_walkable() => {}
+        _ => return None,
+    }
+
+    let mut pushed = Vec::new();
+    let mut chain = true;
+    let mut position = target;
+
+    loop {
+        match occupancy.get(position) {
+            Some(&Occupant::Pushable { entity, chain: next_chain }) => {
+                if !chain {
+                    return None;
+                }
+
+                pushed.push(entity);
+                chain = next_chain;
+                position = (position.0 + direction.0, position.1 + direction.1);
+            }
+            Some(&Occupant::Solid) | Some(&Occupant::Mover) => return None,
+            Some(&Occupant::Other) if !pushed.is_empty() => return None,
+            _ => break,
+        }
+    }
+
+    if pushed.is_empty() {
+        return Some(pushed);
+    }
+
+    match environment.get(position) {
+        Some(soil) if soil.is_walkable() => Some(pushed),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use specs::prelude::*;
+
+    use super::*;
+    use environment::Soil;
+    use level::Terrain;
+
+    const RIGHT: (i32, i32) = (1, 0);
+
+    /// Grass from (0, 0) to (3, 0) with water at (4, 0); everything else is
+    /// off the edge of the level.
+    fn level() -> Environment {
+        let mut environment = Environment::default();
+
+        for x in 0..4 {
+            environment.soil.insert((x, 0), Soil::new(Terrain::Grass));
+        }
+
+        environment.soil.insert((4, 0), Soil::new(Terrain::Water));
+        environment
+    }
+
+    fn blocks(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    fn occupancy(occupants: Vec<((i32, i32), Occupant)>) -> Occupancy {
+        let mut occupancy = Occupancy::default();
+
+        for (position, occupant) in occupants {
+            occupancy.add(position, occupant);
+        }
+
+        occupancy
+    }
+
+    #[test]
+    fn walking_onto_a_free_tile_pushes_nothing() {
+        let occupancy = occupancy(vec![((1, 0), Occupant::Other)]);
+
+        assert_eq!(push_chain((1, 0), RIGHT, &occupancy, &level()), Some(Vec::new()));
+        assert_eq!(push_chain((2, 0), RIGHT, &occupancy, &level()), Some(Vec::new()));
+    }
+
+    #[test]
+    fn solids_and_movers_block_the_way() {
+        let occupancy = occupancy(vec![((1, 0), Occupant::Solid), ((2, 0), Occupant::Mover)]);
+
+        assert_eq!(push_chain((1, 0), RIGHT, &occupancy, &level()), None);
+        assert_eq!(push_chain((2, 0), RIGHT, &occupancy, &level()), None);
+    }
+
+    #[test]
+    fn chained_blocks_push_the_blocks_beyond_them() {
+        let blocks = blocks(2);
+        let occupancy = occupancy(vec![
+            ((1, 0), Occupant::Pushable { entity: blocks[0], chain: true }),
+            ((2, 0), Occupant::Pushable { entity: blocks[1], chain: false }),
+        ]);
+
+        assert_eq!(push_chain((1, 0), RIGHT, &occupancy, &level()), Some(blocks));
+    }
+
+    #[test]
+    fn unchained_blocks_cant_push_another_block() {
+        let blocks = blocks(2);
+        let occupancy = occupancy(vec![
+            ((1, 0), Occupant::Pushable { entity: blocks[0], chain: false }),
+            ((2, 0), Occupant::Pushable { entity: blocks[1], chain: false }),
+        ]);
+
+        assert_eq!(push_chain((1, 0), RIGHT, &occupancy, &level()), None);
+        assert_eq!(push_chain((2, 0), RIGHT, &occupancy, &level()), Some(vec![blocks[1]]));
+    }
+
+    #[test]
+    fn blocks_cant_be_pushed_onto_anything() {
+        let blocks = blocks(1);
+
+        for &beyond in [Occupant::Solid, Occupant::Mover, Occupant::Other].iter() {
+            let occupancy = occupancy(vec![
+                ((1, 0), Occupant::Pushable { entity: blocks[0], chain: true }),
+                ((2, 0), beyond),
+            ]);
+
+            assert_eq!(push_chain((1, 0), RIGHT, &occupancy, &level()), None, "pushed onto {:?}", beyond);
+        }
+    }
+
+    #[test]
+    fn blocks_stay_on_walkable_tiles_of_the_level() {
+        let blocks = blocks(2);
+        let occupancy = occupancy(vec![
+            ((0, 0), Occupant::Pushable { entity: blocks[0], chain: false }),
+            ((3, 0), Occupant::Pushable { entity: blocks[1], chain: false }),
+        ]);
+
+        // Off the edge, and into the water.
+        assert_eq!(push_chain((0, 0), (-1, 0), &occupancy, &level()), None);
+        assert_eq!(push_chain((3, 0), RIGHT, &occupancy, &level()), None);
+        assert_eq!(push_chain((-1, 0), (-1, 0), &occupancy, &level()), None);
+    }
+
+    #[test]
+    fn blocks_win_a_shared_tile() {
+        let blocks = blocks(1);
+        let block = Occupant::Pushable { entity: blocks[0], chain: false };
+        let occupancy = occupancy(vec![((1, 0), Occupant::Other), ((1, 0), block), ((1, 0), Occupant::Solid)]);
+
+        assert_eq!(occupancy.get((1, 0)), Some(&block));
+    }
+}
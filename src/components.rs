@@ -1,9 +1,12 @@
 use std::collections::VecDeque;
-use std::time::Instant;
+use std::time::Duration;
 
 use specs::prelude::*;
 
+use ai::Behaviour;
+use mechanisms::{ReactorKind, TriggerKind};
 use tilemap::Tile;
+use tween::{Easing, Tween, TweenProperty};
 
 #[derive(Debug, Default)]
 pub struct Player {}
@@ -30,23 +33,36 @@ impl Position {
 
 pub struct Sprite {
     pub tile: Tile,
+    /// Breaks ties between sprites on the same layer and row.
+    pub z: i32,
 }
 
 impl Sprite {
-    pub fn new(sprite_layer: i32, sprite_id: i32) -> Sprite {
+    pub fn new(sprite_layer: i32, tileset: &str, sprite_id: i32) -> Sprite {
         Sprite {
-            tile: Tile {
-                sprite_layer,
-                sprite_id,
-            }
+            tile: Tile::new(sprite_layer, tileset, sprite_id),
+            z: 0,
         }
     }
+
+    pub fn with_z(mut self, z: i32) -> Sprite {
+        self.z = z;
+        self
+    }
 }
 
 impl Component for Sprite {
     type Storage = VecStorage<Self>;
 }
 
+/// Draws `shadow.png` beneath the entity's sprite.
+#[derive(Default)]
+pub struct Shadow;
+
+impl Component for Shadow {
+    type Storage = NullStorage<Self>;
+}
+
 #[derive(Default)]
 pub struct Solid;
 
@@ -54,13 +70,47 @@ impl Component for Solid {
     type Storage = NullStorage<Self>;
 }
 
-#[derive(Debug)]
+/// Part of the level that's walked over rather than bumped into, like a
+/// pressure plate or an open door, unless it's also `Solid`.
+#[derive(Default)]
+pub struct Fixture;
+
+impl Component for Fixture {
+    type Storage = NullStorage<Self>;
+}
+
+/// Walking into this entity pushes it one tile along, if the tile beyond is
+/// free.
+#[derive(Debug, Default)]
+pub struct Pushable {
+    /// Whether pushing this block also shoves a block beyond it along,
+    /// rather than being stopped by it.
+    pub chain: bool,
+}
+
+impl Component for Pushable {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Debug, Clone)]
 pub struct Plantae {
+    /// Name of the plant's `Species`.
+    pub species: String,
+    pub stage: u32,
+    /// Growth towards the next stage, from 0 to 1.
+    pub progress: f32,
+    /// Fully grown plants count towards the level's objectives.
+    pub grown: bool,
 }
 
 impl Plantae {
-    pub fn new() -> Self {
-        Plantae {}
+    pub fn new(species: &str) -> Self {
+        Plantae {
+            species: species.to_string(),
+            stage: 0,
+            progress: 0.0,
+            grown: false,
+        }
     }
 }
 
@@ -68,6 +118,82 @@ impl Component for Plantae {
     type Storage = VecStorage<Self>;
 }
 
+/// Something other than the player that moves about on its own.
+#[derive(Debug)]
+pub struct Creature {
+    pub behaviour: Behaviour,
+    /// Index of the patrol route tile the creature is heading for.
+    pub waypoint: usize,
+    /// Eats any plant it ends up standing on.
+    pub hungry: bool,
+}
+
+impl Creature {
+    pub fn new(behaviour: Behaviour, hungry: bool) -> Self {
+        Creature {
+            behaviour,
+            waypoint: 0,
+            hungry,
+        }
+    }
+}
+
+impl Component for Creature {
+    type Storage = VecStorage<Self>;
+}
+
+/// Switches its channel on, see `TriggerKind`.
+#[derive(Debug)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    pub channel: u32,
+    pub active: bool,
+    /// Whether something stood on it at the end of the last turn, so a
+    /// lever only flips when the player steps onto it.
+    pub occupied: bool,
+}
+
+impl Trigger {
+    pub fn new(kind: TriggerKind, channel: u32) -> Self {
+        Trigger {
+            kind,
+            channel,
+            active: false,
+            occupied: false,
+        }
+    }
+}
+
+impl Component for Trigger {
+    type Storage = VecStorage<Self>;
+}
+
+/// Opens while its channel is on, or while it's off if `inverted`.
+#[derive(Debug)]
+pub struct Reactor {
+    pub kind: ReactorKind,
+    pub channel: u32,
+    pub inverted: bool,
+    /// `None` until the reactor has been set up for the level.
+    pub open: Option<bool>,
+}
+
+impl Reactor {
+    pub fn new(kind: ReactorKind, channel: u32, inverted: bool) -> Self {
+        Reactor {
+            kind,
+            channel,
+            inverted,
+            open: None,
+        }
+    }
+}
+
+impl Component for Reactor {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum MoveAction {
     Up,
     Down,
@@ -75,9 +201,23 @@ pub enum MoveAction {
     Right,
 }
 
+impl MoveAction {
+    /// The step this move takes on the grid.
+    pub fn direction(&self) -> (i32, i32) {
+        match *self {
+            MoveAction::Up => (0, -1),
+            MoveAction::Down => (0, 1),
+            MoveAction::Left => (-1, 0),
+            MoveAction::Right => (1, 0),
+        }
+    }
+}
+
 pub struct Movement {
     pub player_owned: bool,
     pub move_queue: VecDeque<MoveAction>,
+    /// A tile to walk to, one step at a time, e.g. where the player clicked.
+    pub destination: Option<(i32, i32)>,
 }
 
 impl Movement {
@@ -85,34 +225,85 @@ impl Movement {
         Movement {
             player_owned,
             move_queue: VecDeque::new(),
+            destination: None,
         }
     }
+
+    /// Forgets any moves still to be made.
+    pub fn cancel(&mut self) {
+        self.move_queue.clear();
+        self.destination = None;
+    }
 }
 
 impl Component for Movement {
     type Storage = VecStorage<Self>;
 }
 
+/// Times are in game time, see `GameClock`.
 pub struct AnimationEventPosition {
     pub offset: (i32, i32),
-    pub start: Instant,
-    pub end: Instant,
+    pub start: Duration,
+    pub end: Duration,
+    pub easing: Easing,
+}
+
+impl AnimationEventPosition {
+    /// Slides from `offset` tiles away back to the entity's position.
+    pub fn tween(&self) -> Tween {
+        Tween {
+            property: TweenProperty::Offset {
+                from: (self.offset.0 as f32, self.offset.1 as f32),
+                to: (0.0, 0.0),
+            },
+            easing: self.easing,
+            start: self.start,
+            end: self.end,
+        }
+    }
 }
 
 pub enum AnimationEvent {
     Position(AnimationEventPosition),
+    Tween(Tween),
+}
+
+impl AnimationEvent {
+    pub fn end(&self) -> Duration {
+        match *self {
+            AnimationEvent::Position(ref position) => position.end,
+            AnimationEvent::Tween(ref tween) => tween.end,
+        }
+    }
 }
 
 pub struct Animation {
     pub animation_queue: VecDeque<AnimationEvent>,
+    /// How long this entity takes to slide from one tile to the next.
+    pub move_duration: Duration,
+    pub move_easing: Easing,
 }
 
 impl Animation {
     pub fn new() -> Self {
         Animation {
             animation_queue: VecDeque::new(),
+            move_duration: Duration::from_millis(100),
+            move_easing: Easing::Linear,
         }
     }
+
+    pub fn with_move(mut self, duration: Duration, easing: Easing) -> Self {
+        self.move_duration = duration;
+        self.move_easing = easing;
+        self
+    }
+
+    /// Whether the entity is still sliding from one tile to the next, or
+    /// playing a tween such as a bump.
+    pub fn is_moving(&self, now: Duration) -> bool {
+        self.animation_queue.iter().any(|event| now < event.end())
+    }
 }
 
 impl Component for Animation {
@@ -1,6 +1,13 @@
+use std::time::Duration;
+
 use specs::prelude::*;
 
+use ai::Behaviour;
 use components::*;
+use mechanisms::{ReactorKind, TriggerKind, MECHANISM_TILESET};
+use species::Species;
+use tilemap::{BACKGROUND_LAYER, ENTITY_LAYER};
+use tween::Easing;
 use world::World;
 
 pub fn create_player(
@@ -13,7 +20,120 @@ pub fn create_player(
         .create_entity()
         .with(Position::new(x, y))
         .with(Movement::new(true))
-        .with(Sprite::new(1, 5))
+        .with(Sprite::new(ENTITY_LAYER, "character", 0))
+        .with(Shadow)
+        .with(Animation::new().with_move(Duration::from_millis(120), Easing::EaseOut))
+        .build()
+}
+
+pub fn create_slime(
+    world: &mut World,
+    x: i32,
+    y: i32,
+    behaviour: Behaviour,
+    hungry: bool,
+) -> Entity {
+    world
+        .specs_world
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Movement::new(false))
+        .with(Creature::new(behaviour, hungry))
+        .with(Sprite::new(ENTITY_LAYER, "slime", 0))
+        .with(Shadow)
+        .with(Animation::new().with_move(Duration::from_millis(180), Easing::EaseInOut))
+        .build()
+}
+
+pub fn create_block(
+    world: &mut World,
+    x: i32,
+    y: i32,
+    chain: bool,
+) -> Entity {
+    world
+        .specs_world
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Sprite::new(ENTITY_LAYER, "block", 0))
+        .with(Pushable { chain })
+        .with(Solid)
+        .with(Animation::new().with_move(Duration::from_millis(120), Easing::EaseOut))
+        .build()
+}
+
+pub fn create_trigger(
+    world: &mut World,
+    x: i32,
+    y: i32,
+    kind: TriggerKind,
+    channel: u32,
+) -> Entity {
+    // Levers stand up like the player does; plates lie on the ground.
+    let sprite = match kind {
+        TriggerKind::Lever => Sprite::new(ENTITY_LAYER, MECHANISM_TILESET, kind.sprite_id(false)),
+        _ => Sprite::new(BACKGROUND_LAYER, MECHANISM_TILESET, kind.sprite_id(false)).with_z(1),
+    };
+
+    world
+        .specs_world
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Trigger::new(kind, channel))
+        .with(sprite)
+        .with(Fixture)
+        .with(Animation::new())
+        .build()
+}
+
+/// Reactors start out closed until their channel is first read.
+pub fn create_reactor(
+    world: &mut World,
+    x: i32,
+    y: i32,
+    kind: ReactorKind,
+    channel: u32,
+    inverted: bool,
+) -> Entity {
+    let sprite = match kind {
+        ReactorKind::Door => Sprite::new(ENTITY_LAYER, MECHANISM_TILESET, kind.sprite_id(false)),
+        _ => Sprite::new(BACKGROUND_LAYER, MECHANISM_TILESET, kind.sprite_id(false)).with_z(1),
+    };
+
+    let mut builder = world
+        .specs_world
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(Reactor::new(kind, channel, inverted))
+        .with(sprite)
+        .with(Fixture)
+        .with(Animation::new());
+
+    if kind.is_solid(false) {
+        builder = builder.with(Solid);
+    }
+
+    builder.build()
+}
+
+pub fn create_plant(
+    world: &mut World,
+    x: i32,
+    y: i32,
+    species: &Species,
+) -> Entity {
+    let plantae = Plantae::new(&species.name);
+    let sprite = Sprite {
+        tile: species.tile(plantae.stage),
+        z: 0,
+    };
+
+    world
+        .specs_world
+        .create_entity()
+        .with(Position::new(x, y))
+        .with(plantae)
+        .with(sprite)
         .with(Animation::new())
         .build()
 }
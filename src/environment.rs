@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use level::{Level, Terrain};
+
+/// Moisture that water tiles hold, and the most any tile can hold.
+pub const SATURATED: f32 = 1.0;
+
+/// Share of the difference to its neighbours' average that a tile takes on
+/// each turn.
+const DIFFUSION_RATE: f32 = 0.5;
+
+/// Moisture every dry tile loses each turn.
+const EVAPORATION: f32 = 0.02;
+
+/// Sunlight left on a tile directly below something tall, and beside that.
+const DIRECT_SHADE: f32 = 0.3;
+const EDGE_SHADE: f32 = 0.7;
+
+/// What a plant gets out of the ground and sky on one tile, each from 0 to 1.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Soil {
+    pub terrain: Terrain,
+    pub moisture: f32,
+    pub sunlight: f32,
+    pub fertility: f32,
+    pub water: bool,
+    /// An extended bridge lets anything cross water.
+    pub bridged: bool,
+}
+
+impl Soil {
+    pub fn new(terrain: Terrain) -> Soil {
+        let (fertility, water) = match terrain {
+            Terrain::Grass => (0.6, false),
+            Terrain::Meadow => (1.0, false),
+            Terrain::Path => (0.0, false),
+            Terrain::Water => (0.0, true),
+        };
+
+        Soil {
+            terrain,
+            moisture: if water { SATURATED } else { 0.0 },
+            sunlight: 1.0,
+            fertility,
+            water,
+            bridged: false,
+        }
+    }
+
+    /// Whether the player, creatures and blocks can stand on this tile.
+    pub fn is_walkable(&self) -> bool {
+        !self.water || self.bridged
+    }
+}
+
+/// Per-tile environment of the level, kept next to the `BackgroundMap`.
+#[derive(Clone, Default)]
+pub struct Environment {
+    pub soil: HashMap<(i32, i32), Soil>,
+}
+
+impl Environment {
+    pub fn load_level(&mut self, level: &Level) {
+        self.soil = level
+            .terrain()
+            .into_iter()
+            .map(|(position, terrain)| (position, Soil::new(terrain)))
+            .collect();
+
+        // Let water soak in before the first turn so plants next to a pond
+        // don't start out dry.
+        for _ in 0..4 {
+            self.diffuse_moisture();
+        }
+    }
+
+    pub fn get(&self, position: (i32, i32)) -> Option<&Soil> {
+        self.soil.get(&position)
+    }
+
+    pub fn get_mut(&mut self, position: (i32, i32)) -> Option<&mut Soil> {
+        self.soil.get_mut(&position)
+    }
+
+    /// Spreads moisture from wet tiles into their dry neighbours. Every tile
+    /// is computed from the previous turn's values, so the result doesn't
+    /// depend on the order tiles are visited in.
+    pub fn diffuse_moisture(&mut self) {
+        let next: HashMap<(i32, i32), f32> = self.soil
+            .iter()
+            .map(|(&(x, y), soil)| {
+                if soil.water {
+                    return ((x, y), SATURATED);
+                }
+
+                let neighbours: Vec<f32> = [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)]
+                    .iter()
+                    .filter_map(|position| self.soil.get(position))
+                    .map(|neighbour| neighbour.moisture)
+                    .collect();
+
+                let average = if neighbours.is_empty() {
+                    soil.moisture
+                } else {
+                    neighbours.iter().sum::<f32>() / neighbours.len() as f32
+                };
+
+                // Tiles only soak up moisture from wetter neighbours, they
+                // dry out through evaporation.
+                let gain = (average - soil.moisture).max(0.0) * DIFFUSION_RATE;
+                let moisture = (soil.moisture + gain - EVAPORATION).max(0.0).min(SATURATED);
+
+                ((x, y), moisture)
+            })
+            .collect();
+
+        for (position, moisture) in next {
+            if let Some(soil) = self.soil.get_mut(&position) {
+                soil.moisture = moisture;
+            }
+        }
+    }
+
+    /// Recomputes sunlight from the tiles with something tall on them. Light
+    /// comes from above, so each of them shades the tile below it and, less,
+    /// the tiles diagonally below.
+    pub fn cast_shade(&mut self, tall: &HashSet<(i32, i32)>) {
+        for soil in self.soil.values_mut() {
+            soil.sunlight = 1.0;
+        }
+
+        for &(x, y) in tall.iter() {
+            let shaded = [
+                ((x, y + 1), DIRECT_SHADE),
+                ((x - 1, y + 1), EDGE_SHADE),
+                ((x + 1, y + 1), EDGE_SHADE),
+            ];
+
+            for &(position, light) in shaded.iter() {
+                if let Some(soil) = self.soil.get_mut(&position) {
+                    soil.sunlight = soil.sunlight.min(light);
+                }
+            }
+        }
+    }
+
+    /// Takes up to `amount` moisture from a tile, returning how much it had.
+    pub fn drink(&mut self, position: (i32, i32), amount: f32) -> f32 {
+        match self.soil.get_mut(&position) {
+            Some(ref mut soil) if !soil.water => {
+                let taken = soil.moisture.min(amount);
+                soil.moisture -= taken;
+                taken
+            }
+            Some(_) => amount,
+            None => 0.0,
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::HashSet;
+
+/// Something a level asks of the player. Levels list these in
+/// `[[objectives]]` tables with a `type`, e.g. `type = "reach_tile"`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Objective {
+    GrowFlowers { count: u32 },
+    ReachTile { x: i32, y: i32 },
+    /// Every listed tile has a fully grown plant on it.
+    FillPattern { tiles: Vec<(i32, i32)> },
+    MoveLimit { moves: u32 },
+    TurnLimit { turns: u32 },
+}
+
+/// Which kind of limit an objective puts on the player.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Limit {
+    Moves,
+    Turns,
+}
+
+impl Objective {
+    pub fn limit(&self) -> Option<Limit> {
+        match *self {
+            Objective::MoveLimit { .. } => Some(Limit::Moves),
+            Objective::TurnLimit { .. } => Some(Limit::Turns),
+            _ => None,
+        }
+    }
+
+    /// Limits can't be achieved, only broken, so they don't count towards
+    /// finishing a level on their own.
+    pub fn is_limit(&self) -> bool {
+        self.limit().is_some()
+    }
+
+    pub fn description(&self) -> String {
+        match *self {
+            Objective::GrowFlowers { count } => format!("Grow {} flowers", count),
+            Objective::ReachTile { x, y } => format!("Reach {}, {}", x, y),
+            Objective::FillPattern { ref tiles } => format!("Fill {} tiles with flowers", tiles.len()),
+            Objective::MoveLimit { moves } => format!("At most {} moves", moves),
+            Objective::TurnLimit { turns } => format!("At most {} turns", turns),
+        }
+    }
+}
+
+/// What the goal system looks at after a turn.
+pub struct Snapshot<'a> {
+    pub moves: u32,
+    pub turns: u32,
+    pub player: Option<(i32, i32)>,
+    /// Tiles with a fully grown plant.
+    pub grown: &'a HashSet<(i32, i32)>,
+    /// How many of those plants are of a `flower` species.
+    pub flowers: u32,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ObjectiveStatus {
+    Pending,
+    Met,
+    Broken,
+}
+
+pub fn evaluate(objective: &Objective, snapshot: &Snapshot) -> ObjectiveStatus {
+    let met = |condition: bool| {
+        if condition {
+            ObjectiveStatus::Met
+        } else {
+            ObjectiveStatus::Pending
+        }
+    };
+    let within = |used: u32, limit: u32| {
+        if used <= limit {
+            ObjectiveStatus::Met
+        } else {
+            ObjectiveStatus::Broken
+        }
+    };
+
+    match *objective {
+        Objective::GrowFlowers { count } => met(snapshot.flowers >= count),
+        Objective::ReachTile { x, y } => met(snapshot.player == Some((x, y))),
+        Objective::FillPattern { ref tiles } => met(tiles.iter().all(|tile| snapshot.grown.contains(tile))),
+        Objective::MoveLimit { moves } => within(snapshot.moves, moves),
+        Objective::TurnLimit { turns } => within(snapshot.turns, turns),
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GoalStatus {
+    InProgress,
+    Complete,
+    Failed(Limit),
+}
+
+/// A level is complete once every objective is met, and failed as soon as
+/// a limit is broken. Levels without objectives can't be completed.
+pub fn goal_status(objectives: &[Objective], snapshot: &Snapshot) -> GoalStatus {
+    let statuses: Vec<ObjectiveStatus> = objectives.iter().map(|objective| evaluate(objective, snapshot)).collect();
+
+    let broken = objectives
+        .iter()
+        .zip(statuses.iter())
+        .filter(|&(_, status)| *status == ObjectiveStatus::Broken)
+        .filter_map(|(objective, _)| objective.limit())
+        .next();
+
+    if let Some(limit) = broken {
+        return GoalStatus::Failed(limit);
+    }
+
+    let has_goal = objectives.iter().any(|objective| !objective.is_limit());
+
+    if has_goal && statuses.iter().all(|status| *status == ObjectiveStatus::Met) {
+        GoalStatus::Complete
+    } else {
+        GoalStatus::InProgress
+    }
+}
+
+/// Three stars at or under par, two within half again as many moves, one
+/// otherwise. Levels without a par always give three.
+pub fn star_rating(moves: u32, par: Option<u32>) -> u32 {
+    match par {
+        None => 3,
+        Some(par) if moves <= par => 3,
+        Some(par) if moves * 2 <= par * 3 => 2,
+        Some(_) => 1,
+    }
+}
+
+/// The objectives of the level being played, and how the player is doing.
+#[derive(Default)]
+pub struct Goals {
+    pub objectives: Vec<Objective>,
+    pub par: Option<u32>,
+    pub status: Option<GoalStatus>,
+    /// Turn the objectives were last checked on.
+    pub evaluated_turn: Option<u32>,
+}
+
+impl Goals {
+    pub fn new(objectives: Vec<Objective>, par: Option<u32>) -> Goals {
+        Goals {
+            objectives,
+            par,
+            status: None,
+            evaluated_turn: None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LevelEvent {
+    Complete { moves: u32, stars: u32 },
+    Failed { limit: Limit },
+}
+
+/// Raised by the goal system and handled by the gameplay scene.
+#[derive(Default)]
+pub struct LevelEvents {
+    pub queue: Vec<LevelEvent>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nothing done yet: no moves or turns, no player and nothing grown.
+    fn snapshot<'a>(grown: &'a HashSet<(i32, i32)>) -> Snapshot<'a> {
+        Snapshot {
+            moves: 0,
+            turns: 0,
+            player: None,
+            grown,
+            flowers: 0,
+        }
+    }
+
+    #[test]
+    fn completes_once_every_goal_is_met_within_the_limits() {
+        let grown = HashSet::new();
+        let objectives = vec![Objective::GrowFlowers { count: 2 }, Objective::MoveLimit { moves: 5 }];
+
+        let growing = Snapshot {
+            moves: 3,
+            flowers: 1,
+            ..snapshot(&grown)
+        };
+        let grown_all = Snapshot {
+            moves: 5,
+            flowers: 2,
+            ..snapshot(&grown)
+        };
+
+        assert_eq!(goal_status(&objectives, &growing), GoalStatus::InProgress);
+        assert_eq!(goal_status(&objectives, &grown_all), GoalStatus::Complete);
+    }
+
+    #[test]
+    fn broken_limits_fail_even_when_the_goals_are_met() {
+        let grown = HashSet::new();
+        let objectives = vec![
+            Objective::ReachTile { x: 2, y: 0 },
+            Objective::MoveLimit { moves: 3 },
+            Objective::TurnLimit { turns: 5 },
+        ];
+
+        let too_many_moves = Snapshot {
+            moves: 4,
+            turns: 4,
+            player: Some((2, 0)),
+            ..snapshot(&grown)
+        };
+        let too_many_turns = Snapshot {
+            moves: 3,
+            turns: 6,
+            player: Some((2, 0)),
+            ..snapshot(&grown)
+        };
+
+        assert_eq!(goal_status(&objectives, &too_many_moves), GoalStatus::Failed(Limit::Moves));
+        assert_eq!(goal_status(&objectives, &too_many_turns), GoalStatus::Failed(Limit::Turns));
+    }
+
+    #[test]
+    fn levels_with_only_limits_never_complete() {
+        let grown = HashSet::new();
+        let within = Snapshot {
+            moves: 1,
+            turns: 1,
+            ..snapshot(&grown)
+        };
+
+        assert_eq!(
+            goal_status(&[Objective::MoveLimit { moves: 10 }, Objective::TurnLimit { turns: 10 }], &within),
+            GoalStatus::InProgress
+        );
+        assert_eq!(goal_status(&[], &within), GoalStatus::InProgress);
+    }
+
+    #[test]
+    fn fill_pattern_needs_every_tile_grown() {
+        let mut grown = HashSet::new();
+        grown.insert((0, 0));
+        let objectives = vec![Objective::FillPattern {
+            tiles: vec![(0, 0), (1, 0)],
+        }];
+
+        assert_eq!(goal_status(&objectives, &snapshot(&grown)), GoalStatus::InProgress);
+
+        grown.insert((1, 0));
+        assert_eq!(goal_status(&objectives, &snapshot(&grown)), GoalStatus::Complete);
+    }
+
+    #[test]
+    fn stars_drop_past_par_and_half_again() {
+        assert_eq!(star_rating(8, Some(10)), 3);
+        assert_eq!(star_rating(10, Some(10)), 3);
+        assert_eq!(star_rating(11, Some(10)), 2);
+        assert_eq!(star_rating(15, Some(10)), 2);
+        assert_eq!(star_rating(16, Some(10)), 1);
+        assert_eq!(star_rating(100, None), 3);
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::time::Duration;
 
 use ggez::graphics;
 use ggez::{Context, GameResult};
@@ -8,6 +9,8 @@ use input::Buttons;
 #[derive(Debug)]
 pub enum WidgetEvent {
     ButtonEvent(ButtonEvent),
+    DialogEvent(DialogEvent),
+    MenuEvent(MenuEvent),
 }
 
 #[derive(Debug)]
@@ -15,12 +18,26 @@ pub enum ButtonEvent {
     Clicked,
 }
 
+#[derive(Debug)]
+pub enum MenuEvent {
+    Moved(i32),
+    Selected(i32),
+}
+
+#[derive(Debug)]
+pub enum DialogEvent {
+    PageTurned(usize),
+    Finished,
+}
+
 pub trait Renderable {
     fn render(&self, ctx: &mut Context) -> GameResult<()>;
 }
 
 pub trait Widget: Renderable {
-    fn interact(&self, interaction: Buttons, messages: &mut VecDeque<WidgetEvent>);
+    fn interact(&mut self, interaction: Buttons, messages: &mut VecDeque<WidgetEvent>);
+
+    fn update(&mut self, _dt: Duration) {}
 }
 
 pub struct GuiManager {
@@ -34,17 +51,21 @@ impl GuiManager {
         }
     }
 
-    pub fn interact(&self, interaction: Buttons) -> VecDeque<WidgetEvent> {
+    pub fn interact(&mut self, interaction: Buttons) -> VecDeque<WidgetEvent> {
         let mut messages: VecDeque<WidgetEvent> = VecDeque::new();
 
-        for widget in self.widgets.iter() {
+        for widget in self.widgets.iter_mut() {
             widget.interact(interaction, &mut messages);
         }
 
         messages
     }
 
-    pub fn update(&self) {}
+    pub fn update(&mut self, dt: Duration) {
+        for widget in self.widgets.iter_mut() {
+            widget.update(dt);
+        }
+    }
 
     pub fn render(&self, ctx: &mut Context) -> GameResult<()> {
         for widget in self.widgets.iter() {
@@ -77,7 +98,7 @@ impl Renderable for Button {
 }
 
 impl Widget for Button {
-    fn interact(&self, _interaction: Buttons, messages: &mut VecDeque<WidgetEvent>) {
+    fn interact(&mut self, _interaction: Buttons, messages: &mut VecDeque<WidgetEvent>) {
         messages.push_back(WidgetEvent::ButtonEvent(ButtonEvent::Clicked));
     }
 }
@@ -0,0 +1,49 @@
+use specs::Entity;
+
+use components::Plantae;
+use environment::Environment;
+
+/// A plant as it was before a turn. Plants come and go as they spread, so
+/// undoing a turn replaces all of them rather than restoring by entity.
+#[derive(Debug, Clone)]
+pub struct PlantRecord {
+    pub position: (i32, i32),
+    pub plant: Plantae,
+}
+
+/// Everything a turn can change, as it was before the turn was taken.
+#[derive(Clone)]
+pub struct TurnRecord {
+    pub turns: u32,
+    pub moves: u32,
+    /// Every entity that isn't a plant, e.g. the player and blocks.
+    pub positions: Vec<(Entity, (i32, i32))>,
+    pub plants: Vec<PlantRecord>,
+    /// Whether each trigger was on, as levers stay flipped between turns.
+    pub triggers: Vec<(Entity, bool)>,
+    pub environment: Environment,
+}
+
+/// The turns taken in the level, most recent last.
+#[derive(Default)]
+pub struct History {
+    records: Vec<TurnRecord>,
+}
+
+impl History {
+    pub fn push(&mut self, record: TurnRecord) {
+        self.records.push(record);
+    }
+
+    pub fn pop(&mut self) -> Option<TurnRecord> {
+        self.records.pop()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
 use ggez::event::Keycode;
+use ggez::graphics::Point2;
 
 #[derive(Debug, Hash, Copy, Clone, Eq, PartialEq)]
 pub enum Buttons {
@@ -9,6 +10,15 @@ pub enum Buttons {
     Left,
     Right,
     Action,
+    Cancel,
+    Undo,
+}
+
+/// Mouse input, in logical coordinates.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PointerEvent {
+    Moved(Point2),
+    Clicked(Point2),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -80,6 +90,9 @@ impl InputBinding {
         bindings.insert(Keycode::Left, Buttons::Left);
         bindings.insert(Keycode::Right, Buttons::Right);
         bindings.insert(Keycode::Return, Buttons::Action);
+        bindings.insert(Keycode::Escape, Buttons::Cancel);
+        bindings.insert(Keycode::Z, Buttons::Undo);
+        bindings.insert(Keycode::Backspace, Buttons::Undo);
 
         InputBinding { bindings }
     }
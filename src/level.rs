@@ -0,0 +1,205 @@
+use ggez::Context;
+use warmy;
+
+use assets::{self, AssetError};
+use ai::Behaviour;
+use goals::Objective;
+use mechanisms::{ReactorKind, TriggerKind};
+use tilemap::{Tile, BACKGROUND_LAYER};
+
+pub const LEVEL_INDEX_PATH: &str = "/levels/index.toml";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelEntry {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelIndex {
+    pub levels: Vec<LevelEntry>,
+}
+
+impl warmy::Load<Context> for LevelIndex {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        assets::read_toml(&key, store.root(), ctx).map(warmy::Loaded::from)
+    }
+}
+
+/// A plant the level starts with, as a seed.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlantSpawn {
+    pub x: i32,
+    pub y: i32,
+    pub species: String,
+}
+
+/// A block the player can push around.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlockSpawn {
+    pub x: i32,
+    pub y: i32,
+    /// Whether it shoves a block beyond it along when pushed.
+    #[serde(default)]
+    pub chain: bool,
+}
+
+/// A slime and how it behaves.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreatureSpawn {
+    pub x: i32,
+    pub y: i32,
+    pub behaviour: Behaviour,
+    /// Eats plants it walks over.
+    #[serde(default)]
+    pub hungry: bool,
+}
+
+/// A pressure plate, lever or the like, switching on `channel`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TriggerSpawn {
+    pub x: i32,
+    pub y: i32,
+    pub kind: TriggerKind,
+    pub channel: u32,
+}
+
+/// A door, bridge or water gate, opened by the triggers on `channel`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReactorSpawn {
+    pub x: i32,
+    pub y: i32,
+    pub kind: ReactorKind,
+    pub channel: u32,
+    /// Open while the channel is off instead.
+    #[serde(default)]
+    pub inverted: bool,
+}
+
+/// A hand-made puzzle layout. Each string in `tiles` is one row of the
+/// background map, using the characters understood by `background_tile`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Level {
+    pub name: String,
+    pub player: (i32, i32),
+    pub intro: Option<String>,
+    pub tiles: Vec<String>,
+    #[serde(default)]
+    pub objectives: Vec<Objective>,
+    /// Moves a good solution takes, for the star rating.
+    #[serde(default)]
+    pub par: Option<u32>,
+    #[serde(default)]
+    pub plants: Vec<PlantSpawn>,
+    #[serde(default)]
+    pub blocks: Vec<BlockSpawn>,
+    #[serde(default)]
+    pub creatures: Vec<CreatureSpawn>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerSpawn>,
+    #[serde(default)]
+    pub reactors: Vec<ReactorSpawn>,
+    /// A script run alongside the level, e.g. `/scripts/first-sprouts.rhai`.
+    #[serde(default)]
+    pub script: Option<String>,
+    #[serde(skip)]
+    pub generation: usize,
+}
+
+impl Level {
+    pub fn width(&self) -> i32 {
+        self.tiles.iter().map(|row| row.chars().count()).max().unwrap_or(0) as i32
+    }
+
+    pub fn height(&self) -> i32 {
+        self.tiles.len() as i32
+    }
+
+    /// Every tile in the layout as `((x, y), terrain)`.
+    pub fn terrain(&self) -> Vec<((i32, i32), Terrain)> {
+        let mut terrain = Vec::new();
+
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if let Some(kind) = Terrain::from_char(c) {
+                    terrain.push(((x as i32, y as i32), kind));
+                }
+            }
+        }
+
+        terrain
+    }
+
+    /// The background tile drawn for every tile in the layout.
+    pub fn background(&self) -> Vec<((i32, i32), Tile)> {
+        let mut background = Vec::new();
+
+        for (y, row) in self.tiles.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if let Some(tile) = background_tile(c) {
+                    background.push(((x as i32, y as i32), tile));
+                }
+            }
+        }
+
+        background
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Terrain {
+    Grass,
+    /// Grass with flowers in it, the most fertile ground.
+    Meadow,
+    Path,
+    Water,
+}
+
+impl Terrain {
+    fn from_char(c: char) -> Option<Terrain> {
+        match c {
+            '.' | ',' | '\'' => Some(Terrain::Grass),
+            '*' => Some(Terrain::Meadow),
+            '#' => Some(Terrain::Path),
+            '~' => Some(Terrain::Water),
+            _ => None,
+        }
+    }
+}
+
+impl warmy::Load<Context> for Level {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let mut level: Level = assets::read_toml(&key, store.root(), ctx)?;
+        level.generation = assets::next_generation();
+
+        Ok(warmy::Loaded::from(level))
+    }
+}
+
+fn background_tile(c: char) -> Option<Tile> {
+    let (tileset, sprite_id) = match c {
+        '.' => ("grass", 0),
+        ',' => ("grass", 1),
+        '#' => ("grass", 2),
+        '*' => ("grass", 3),
+        '\'' => ("grass", 4),
+        '~' => ("water", 0),
+        _ => return None,
+    };
+
+    Some(Tile::new(BACKGROUND_LAYER, tileset, sprite_id))
+}
@@ -1,37 +1,77 @@
 #![allow(dead_code)]
 #![windows_subsystem = "windows"]
 
+extern crate byteorder;
 extern crate ggez;
+extern crate gif;
+extern crate image;
+extern crate inflate;
 extern crate rand;
+extern crate rhai;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate specs;
+extern crate toml;
 extern crate warmy;
 
 use std::path;
 
-use ggez::conf::{WindowMode, WindowSetup};
 use ggez::event;
-use ggez::ContextBuilder;
+use ggez::filesystem::Filesystem;
+use ggez::{ContextBuilder, GameResult};
 
+mod ai;
+mod animated_gif;
+mod animation;
 mod app;
+mod aseprite;
+mod atlas;
 mod assets;
+mod audio;
+mod collision;
 mod components;
 mod entities;
+mod environment;
+mod goals;
 mod gui;
+mod history;
 mod input;
+mod level;
+mod mechanisms;
+mod manifest;
+mod pathfinding;
 mod resources;
+mod scenes;
 mod screen;
+mod scripting;
+mod settings;
+mod species;
+mod spread;
 mod state;
 mod systems;
 mod tilemap;
+mod tween;
 mod widgets;
 mod world;
 
 use app::AppState;
+use settings::Settings;
+
+fn main() -> GameResult<()> {
+    // The saved settings are read before the context exists, so the window
+    // opens in the right mode straight away.
+    let mut filesystem = Filesystem::new("puzzle", "vy")?;
+    let (settings, settings_error) = match Settings::load(&mut filesystem) {
+        Ok(settings) => (settings, None),
+        // The game still starts with the defaults, and the loading screen
+        // says what was wrong with the file.
+        Err(e) => (Settings::default(), Some(e.to_string())),
+    };
 
-fn main() {
     let mut context_builder = ContextBuilder::new("puzzle", "vy")
-        .window_setup(WindowSetup::default().title("Puzzle").allow_highdpi(true).samples(1).expect("Invalid window config"))
-        .window_mode(WindowMode::default().dimensions(1024, 720));
+        .window_setup(settings.window_setup())
+        .window_mode(settings.window_mode());
 
     let cargo_path: Option<path::PathBuf> = option_env!("CARGO_MANIFEST_DIR").map(|env_path| {
         let mut res_path = path::PathBuf::from(env_path);
@@ -43,8 +83,8 @@ fn main() {
         context_builder = context_builder.add_resource_path(s);
     }
 
-    let ctx = &mut context_builder.build().unwrap();
-    let state = &mut AppState::new(cargo_path, ctx).unwrap();
+    let ctx = &mut context_builder.build()?;
+    let state = &mut AppState::new(cargo_path, ctx, settings, settings_error)?;
 
-    event::run(ctx, state).unwrap();
+    event::run(ctx, state)
 }
@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use ggez::graphics::Font;
+use ggez::{Context, GameResult};
+use warmy;
+
+use animated_gif::AnimatedGif;
+use assets::{self, AssetError, Assets, Image, Sound};
+use level::Level;
+use species::SpeciesRegistry;
+use tilemap::TileSetRegistry;
+
+pub const MANIFEST_PATH: &str = "/manifest.toml";
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AssetKind {
+    Image,
+    Animation,
+    Font,
+    Level,
+    Sound,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub path: String,
+}
+
+/// Every asset the game needs, under the logical names scenes look them up
+/// by. Loaded up front by the `Preloader`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Manifest {
+    pub images: Vec<ManifestEntry>,
+    pub animations: Vec<ManifestEntry>,
+    pub fonts: Vec<ManifestEntry>,
+    pub levels: Vec<ManifestEntry>,
+    pub sounds: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn entries(&self) -> Vec<(AssetKind, ManifestEntry)> {
+        let sections = [
+            (AssetKind::Image, &self.images),
+            (AssetKind::Animation, &self.animations),
+            (AssetKind::Font, &self.fonts),
+            (AssetKind::Level, &self.levels),
+            (AssetKind::Sound, &self.sounds),
+        ];
+
+        sections
+            .iter()
+            .flat_map(|&(kind, entries)| entries.iter().map(move |entry| (kind, entry.clone())))
+            .collect()
+    }
+}
+
+impl warmy::Load<Context> for Manifest {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        assets::read_toml(&key, store.root(), ctx).map(warmy::Loaded::from)
+    }
+}
+
+enum PreloadStep {
+    Asset(AssetKind, ManifestEntry),
+    Species,
+    /// Tilesets go last, since packing the atlas needs all of their images.
+    Tilesets,
+}
+
+/// Loads the manifest one asset at a time so a loading screen can draw
+/// progress in between. Failures are collected rather than returned, so
+/// every missing asset is reported at once at the end.
+pub struct Preloader {
+    pending: VecDeque<PreloadStep>,
+    total: usize,
+    pub errors: Vec<String>,
+}
+
+impl Preloader {
+    pub fn new(assets: &mut Assets, ctx: &mut Context) -> GameResult<Preloader> {
+        let manifest = assets::get::<Manifest>(&mut assets.asset_store, MANIFEST_PATH, ctx)?;
+
+        let mut pending: VecDeque<PreloadStep> = manifest
+            .borrow()
+            .entries()
+            .into_iter()
+            .map(|(kind, entry)| PreloadStep::Asset(kind, entry))
+            .collect();
+        pending.push_back(PreloadStep::Species);
+        pending.push_back(PreloadStep::Tilesets);
+
+        Ok(Preloader {
+            total: pending.len(),
+            pending,
+            errors: Vec::new(),
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Fraction of the steps finished, from 0 to 1.
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+
+        (self.total - self.pending.len()) as f32 / self.total as f32
+    }
+
+    /// What the next call to `step` will load.
+    pub fn current(&self) -> Option<&str> {
+        match self.pending.front() {
+            Some(&PreloadStep::Asset(_, ref entry)) => Some(&entry.path),
+            Some(&PreloadStep::Species) => Some("plant species"),
+            Some(&PreloadStep::Tilesets) => Some("tilesets"),
+            None => None,
+        }
+    }
+
+    /// Loads the next asset into `assets`.
+    pub fn step(&mut self, assets: &mut Assets, ctx: &mut Context) {
+        let result = match self.pending.pop_front() {
+            Some(PreloadStep::Asset(kind, entry)) => {
+                Preloader::load(kind, &entry, assets, ctx).map_err(|e| format!("{}: {}", entry.name, e))
+            }
+            Some(PreloadStep::Species) => SpeciesRegistry::load(&mut assets.asset_store, ctx)
+                .map(|species| assets.species = species)
+                .map_err(|e| format!("plant species: {}", e)),
+            Some(PreloadStep::Tilesets) => TileSetRegistry::load(&mut assets.asset_store, ctx)
+                .map(|tilesets| assets.tilesets = tilesets)
+                .map_err(|e| format!("tilesets: {}", e)),
+            None => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.errors.push(e);
+        }
+
+        // Tilesets fall back to placeholders rather than fail, and reloads
+        // may have gone wrong while loading; both are listed with the rest.
+        self.errors.extend(assets.tilesets.errors.drain(..));
+        self.errors.extend(assets.errors.drain(..));
+    }
+
+    fn load(kind: AssetKind, entry: &ManifestEntry, assets: &mut Assets, ctx: &mut Context) -> GameResult<()> {
+        let store = &mut assets.asset_store;
+
+        match kind {
+            AssetKind::Image => {
+                let image = assets::get::<Image>(store, &entry.path, ctx)?;
+                assets.images.insert(entry.name.clone(), image);
+            }
+            AssetKind::Animation => {
+                let animation = assets::get::<AnimatedGif>(store, &entry.path, ctx)?;
+                assets.animations.insert(entry.name.clone(), animation);
+            }
+            AssetKind::Font => {
+                let font = Font::new_glyph_font(ctx, &entry.path)?;
+                assets.fonts.insert(entry.name.clone(), font);
+            }
+            AssetKind::Level => {
+                // Kept warm in the store for when the level is played.
+                assets::get::<Level>(store, &entry.path, ctx)?;
+            }
+            AssetKind::Sound => {
+                let sound = assets::get::<Sound>(store, &entry.path, ctx)?;
+                assets.sounds.insert(entry.name.clone(), sound);
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+/// Sprites of the mechanisms, in `mechanisms.png`.
+pub const MECHANISM_TILESET: &str = "mechanisms";
+
+/// What switches a trigger on.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerKind {
+    /// On while the player, a creature or a block stands on it.
+    PressurePlate,
+    /// Flips every time the player steps onto it.
+    Lever,
+    /// On while a grown plant stands on it.
+    PlantGrown,
+}
+
+impl TriggerKind {
+    pub fn sprite_id(&self, active: bool) -> i32 {
+        let first = match *self {
+            TriggerKind::PressurePlate => 0,
+            TriggerKind::Lever => 2,
+            TriggerKind::PlantGrown => 10,
+        };
+
+        if active {
+            first + 1
+        } else {
+            first
+        }
+    }
+}
+
+/// What a reactor does when its channel switches it on.
+#[derive(Debug, Copy, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReactorKind {
+    /// `Solid` while closed.
+    Door,
+    /// Lets anything cross the water it's over while extended.
+    Bridge,
+    /// `Solid` while closed; when opened, floods its tile.
+    WaterGate,
+}
+
+impl ReactorKind {
+    pub fn sprite_id(&self, open: bool) -> i32 {
+        let first = match *self {
+            ReactorKind::Door => 4,
+            ReactorKind::Bridge => 6,
+            ReactorKind::WaterGate => 8,
+        };
+
+        if open {
+            first + 1
+        } else {
+            first
+        }
+    }
+
+    pub fn is_solid(&self, open: bool) -> bool {
+        match *self {
+            ReactorKind::Door | ReactorKind::WaterGate => !open,
+            ReactorKind::Bridge => false,
+        }
+    }
+}
+
+/// Works out which channels are on from the `(channel, active)` state of
+/// every trigger. A channel is on once all of its triggers are, so a door
+/// can need several plates held down at once.
+pub fn channel_signals(triggers: &[(u32, bool)]) -> HashMap<u32, bool> {
+    let mut signals = HashMap::new();
+
+    for &(channel, active) in triggers.iter() {
+        let signal = signals.entry(channel).or_insert(true);
+        *signal = *signal && active;
+    }
+
+    signals
+}
+
+/// Whether a reactor on `channel` is open. Channels without any triggers
+/// are off.
+pub fn is_open(signals: &HashMap<u32, bool>, channel: u32, inverted: bool) -> bool {
+    signals.get(&channel).cloned().unwrap_or(false) != inverted
+}
@@ -0,0 +1,346 @@
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use components::MoveAction;
+use environment::Soil;
+use level::Terrain;
+
+const MOVES: [MoveAction; 4] = [MoveAction::Up, MoveAction::Right, MoveAction::Down, MoveAction::Left];
+
+/// Paths kept before the cache is thrown away and started over.
+const CACHE_LIMIT: usize = 4096;
+
+/// What it costs to step onto a tile of `terrain`, or `None` if it can't be
+/// walked on. Paths keep to the garden path where they can and go around
+/// flower beds.
+pub fn terrain_cost(terrain: Terrain) -> Option<u32> {
+    match terrain {
+        Terrain::Path => Some(1),
+        Terrain::Grass => Some(2),
+        Terrain::Meadow => Some(3),
+        Terrain::Water => None,
+    }
+}
+
+/// What it costs to step onto `soil`. A bridge is as quick to cross as the
+/// garden path.
+pub fn soil_cost(soil: &Soil) -> Option<u32> {
+    if !soil.is_walkable() {
+        None
+    } else if soil.bridged {
+        Some(1)
+    } else {
+        terrain_cost(soil.terrain)
+    }
+}
+
+/// The tiles a path can cross and what each costs to step onto. Tiles that
+/// aren't in it, like walls and blocks, can't be crossed.
+#[derive(Debug, Clone, Default)]
+pub struct PathGrid {
+    costs: HashMap<(i32, i32), u32>,
+    signature: u64,
+}
+
+impl PathGrid {
+    pub fn new(costs: HashMap<(i32, i32), u32>) -> PathGrid {
+        let mut entries: Vec<(&(i32, i32), &u32)> = costs.iter().collect();
+        entries.sort();
+
+        let mut hasher = DefaultHasher::new();
+        entries.hash(&mut hasher);
+        let signature = hasher.finish();
+
+        PathGrid { costs, signature }
+    }
+
+    pub fn cost(&self, position: (i32, i32)) -> Option<u32> {
+        self.costs.get(&position).cloned()
+    }
+
+    /// The same for grids with the same tiles and costs, so cached paths
+    /// can tell whether they still hold.
+    pub fn signature(&self) -> u64 {
+        self.signature
+    }
+}
+
+fn step(position: (i32, i32), action: MoveAction) -> (i32, i32) {
+    let (dx, dy) = action.direction();
+    (position.0 + dx, position.1 + dy)
+}
+
+fn distance(a: (i32, i32), b: (i32, i32)) -> u32 {
+    ((a.0 - b.0).abs() + (a.1 - b.1).abs()) as u32
+}
+
+/// The cheapest path from `start` to `goal` with A*, as the moves to make,
+/// never stepping on a tile in `blocked`. There's no path to a goal that
+/// isn't on the grid, like water or a wall. A goal in `blocked` can still
+/// be reached, so a path can lead up to someone in the way; it's up to the
+/// caller whether to take that last step. No tile costs less than 1, so
+/// with equal costs this finds the same paths as a breadth-first search
+/// would.
+pub fn find_path(
+    grid: &PathGrid,
+    start: (i32, i32),
+    goal: (i32, i32),
+    blocked: &HashSet<(i32, i32)>,
+) -> Option<Vec<MoveAction>> {
+    if start == goal {
+        return Some(Vec::new());
+    }
+
+    // Goals off the grid can't be reached.
+    grid.cost(goal)?;
+
+    let mut open = BinaryHeap::new();
+    let mut costs: HashMap<(i32, i32), u32> = HashMap::new();
+    let mut came_from: HashMap<(i32, i32), ((i32, i32), MoveAction)> = HashMap::new();
+
+    costs.insert(start, 0);
+    open.push(Reverse((distance(start, goal), 0, start)));
+
+    while let Some(Reverse((_, cost, position))) = open.pop() {
+        if position == goal {
+            break;
+        }
+
+        // Stale entry for a tile that was reached more cheaply since.
+        if costs.get(&position).map_or(false, |&best| best < cost) {
+            continue;
+        }
+
+        for &action in MOVES.iter() {
+            let next = step(position, action);
+
+            if next != goal && blocked.contains(&next) {
+                continue;
+            }
+
+            let step_cost = match grid.cost(next) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+
+            let next_cost = cost + step_cost.max(1);
+
+            if costs.get(&next).map_or(false, |&best| best <= next_cost) {
+                continue;
+            }
+
+            costs.insert(next, next_cost);
+            came_from.insert(next, (position, action));
+            open.push(Reverse((next_cost + distance(next, goal), next_cost, next)));
+        }
+    }
+
+    if !came_from.contains_key(&goal) {
+        return None;
+    }
+
+    let mut path = Vec::new();
+    let mut position = goal;
+
+    while let Some(&(previous, action)) = came_from.get(&position) {
+        path.push(action);
+        position = previous;
+    }
+
+    path.reverse();
+
+    Some(path)
+}
+
+/// Remembers paths found on a grid until the grid changes. Creatures and
+/// the player come and go every turn, so paths are cached on the grid of
+/// walls and blocks alone and only searched afresh when one of them is in
+/// the way.
+#[derive(Default)]
+pub struct Pathfinder {
+    signature: u64,
+    paths: HashMap<((i32, i32), (i32, i32)), Option<Vec<MoveAction>>>,
+}
+
+impl Pathfinder {
+    pub fn find(&mut self, grid: &PathGrid, start: (i32, i32), goal: (i32, i32)) -> Option<Vec<MoveAction>> {
+        if grid.signature() != self.signature || self.paths.len() > CACHE_LIMIT {
+            self.signature = grid.signature();
+            self.paths.clear();
+        }
+
+        if let Some(path) = self.paths.get(&(start, goal)) {
+            return path.clone();
+        }
+
+        let path = find_path(grid, start, goal, &HashSet::new());
+
+        match path {
+            // Every step along the way has the rest of the path as its own
+            // cheapest path to the goal.
+            Some(ref path) => {
+                let mut position = start;
+
+                for (index, &action) in path.iter().enumerate() {
+                    self.paths.insert((position, goal), Some(path[index..].to_vec()));
+                    position = step(position, action);
+                }
+            }
+            None => {
+                self.paths.insert((start, goal), None);
+            }
+        }
+
+        path
+    }
+
+    /// Like `find`, but around the tiles in `blocked` as well, e.g. where
+    /// other movers stand this turn.
+    pub fn find_avoiding(
+        &mut self,
+        grid: &PathGrid,
+        start: (i32, i32),
+        goal: (i32, i32),
+        blocked: &HashSet<(i32, i32)>,
+    ) -> Option<Vec<MoveAction>> {
+        let path = self.find(grid, start, goal)?;
+
+        let mut position = start;
+        let crosses_blocked = path.iter().any(|&action| {
+            position = step(position, action);
+            position != goal && blocked.contains(&position)
+        });
+
+        if crosses_blocked {
+            find_path(grid, start, goal, blocked)
+        } else {
+            Some(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use components::MoveAction::{Down, Right, Up};
+
+    /// A grid drawn as rows of text: `.` is the garden path, `,` grass, `"`
+    /// meadow and anything else can't be crossed.
+    fn grid(rows: &[&str]) -> PathGrid {
+        let mut costs = HashMap::new();
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                let cost = match tile {
+                    '.' => 1,
+                    ',' => 2,
+                    '"' => 3,
+                    _ => continue,
+                };
+
+                costs.insert((x as i32, y as i32), cost);
+            }
+        }
+
+        PathGrid::new(costs)
+    }
+
+    fn walk(start: (i32, i32), path: &[MoveAction]) -> Vec<(i32, i32)> {
+        let mut position = start;
+
+        path.iter()
+            .map(|&action| {
+                position = step(position, action);
+                position
+            })
+            .collect()
+    }
+
+    #[test]
+    fn goes_straight_across_open_ground() {
+        let grid = grid(&["....."]);
+
+        assert_eq!(
+            find_path(&grid, (0, 0), (4, 0), &HashSet::new()),
+            Some(vec![Right, Right, Right, Right])
+        );
+        assert_eq!(find_path(&grid, (2, 0), (2, 0), &HashSet::new()), Some(vec![]));
+    }
+
+    #[test]
+    fn detours_around_walls() {
+        let grid = grid(&[".#.", ".#.", "..."]);
+
+        assert_eq!(
+            find_path(&grid, (0, 0), (2, 0), &HashSet::new()),
+            Some(vec![Down, Down, Right, Right, Up, Up])
+        );
+    }
+
+    #[test]
+    fn prefers_cheaper_tiles() {
+        let grid = grid(&[".\"\"\".", "....."]);
+
+        assert_eq!(
+            find_path(&grid, (0, 0), (4, 0), &HashSet::new()),
+            Some(vec![Down, Right, Right, Right, Right, Up])
+        );
+    }
+
+    #[test]
+    fn goes_around_blocked_tiles_but_can_end_on_one() {
+        let grid = grid(&["...", "..."]);
+        let mut blocked = HashSet::new();
+        blocked.insert((1, 0));
+
+        let path = find_path(&grid, (0, 0), (2, 0), &blocked).expect("there's a way around");
+
+        assert_eq!(walk((0, 0), &path), vec![(0, 1), (1, 1), (2, 1), (2, 0)]);
+        assert_eq!(find_path(&grid, (0, 0), (1, 0), &blocked), Some(vec![Right]));
+    }
+
+    #[test]
+    fn finds_nothing_without_a_way_to_the_goal() {
+        let walled_off = grid(&[".#.", ".#."]);
+        let ends_in_water = grid(&["..~"]);
+
+        assert_eq!(find_path(&walled_off, (0, 0), (2, 0), &HashSet::new()), None);
+        assert_eq!(find_path(&ends_in_water, (0, 0), (2, 0), &HashSet::new()), None);
+        assert_eq!(find_path(&ends_in_water, (0, 0), (0, -1), &HashSet::new()), None);
+    }
+
+    #[test]
+    fn pathfinder_goes_around_movers() {
+        let grid = grid(&["...", "..."]);
+        let mut pathfinder = Pathfinder::default();
+        let mut blocked = HashSet::new();
+
+        assert_eq!(pathfinder.find(&grid, (0, 0), (2, 0)), Some(vec![Right, Right]));
+
+        blocked.insert((1, 0));
+        let path = pathfinder
+            .find_avoiding(&grid, (0, 0), (2, 0), &blocked)
+            .expect("there's a way around");
+
+        assert!(!walk((0, 0), &path).contains(&(1, 0)));
+        assert_eq!(path.len(), 4);
+    }
+
+    #[test]
+    fn pathfinder_forgets_paths_when_the_grid_changes() {
+        let open = grid(&["...", "..."]);
+        let walled = grid(&[".#.", "..."]);
+        let mut pathfinder = Pathfinder::default();
+
+        assert_eq!(pathfinder.find(&open, (0, 0), (2, 0)), Some(vec![Right, Right]));
+        // Cached from the search above.
+        assert_eq!(pathfinder.find(&open, (1, 0), (2, 0)), Some(vec![Right]));
+        assert_eq!(
+            pathfinder.find(&walled, (0, 0), (2, 0)),
+            Some(vec![Down, Right, Right, Up])
+        );
+    }
+}
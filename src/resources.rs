@@ -1,8 +1,13 @@
 use std::collections::HashMap;
+use std::time::Duration;
+
+use ggez::timer;
 
 use rand::{distributions, thread_rng, Rng};
 
-use tilemap::Tile;
+use audio::SoundEffect;
+use level::Level;
+use tilemap::{Tile, BACKGROUND_LAYER};
 
 #[derive(Default)]
 pub struct EntityMap {
@@ -17,6 +22,95 @@ impl EntityMap {
     }
 }
 
+/// Game time, which only moves while the gameplay dispatcher runs. Pausing
+/// or slowing it down freezes or slows every animation along with it, and
+/// tests can step it without waiting on the wall clock.
+pub struct GameClock {
+    now: Duration,
+    step: Duration,
+    pub time_scale: f64,
+    pub paused: bool,
+    /// Ticks still allowed while paused, for stepping frame by frame.
+    pending_steps: u32,
+}
+
+impl Default for GameClock {
+    fn default() -> Self {
+        GameClock::new(Duration::from_millis(16))
+    }
+}
+
+impl GameClock {
+    /// A clock that moves `step` forward on every tick, e.g. one frame of
+    /// the fixed update loop.
+    pub fn new(step: Duration) -> Self {
+        GameClock {
+            now: Duration::from_millis(0),
+            step,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+        }
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn tick(&mut self) {
+        if self.paused {
+            if self.pending_steps == 0 {
+                return;
+            }
+
+            self.pending_steps -= 1;
+        }
+
+        let scaled = timer::duration_to_f64(self.step) * self.time_scale.max(0.0);
+        self.now += timer::f64_to_duration(scaled);
+    }
+
+    /// Lets the next `steps` ticks through even though the clock is paused.
+    pub fn step_frames(&mut self, steps: u32) {
+        self.pending_steps += steps;
+    }
+
+    /// Moves the clock regardless of pausing and scaling.
+    pub fn advance(&mut self, dt: Duration) {
+        self.now += dt;
+    }
+}
+
+/// Counts the player's actions. Every action is a turn; moves are the
+/// turns in which the player actually changed tiles.
+#[derive(Default)]
+pub struct TurnCounter {
+    pub turns: u32,
+    pub moves: u32,
+}
+
+/// The tiles under the mouse and last clicked, set by the scene from
+/// pointer events.
+#[derive(Default)]
+pub struct PointerState {
+    pub hovered: Option<(i32, i32)>,
+    pub clicked: Option<(i32, i32)>,
+}
+
+/// The tiles the player is walking along to their destination, or would
+/// walk along to the hovered tile, for drawing.
+#[derive(Default)]
+pub struct PathPreview {
+    pub tiles: Vec<(i32, i32)>,
+}
+
+/// Sounds requested by systems this frame, played by the scene after the
+/// dispatcher has run.
+#[derive(Default)]
+pub struct SoundEffects {
+    pub queue: Vec<SoundEffect>,
+}
+
 #[derive(Default)]
 pub struct BackgroundMap {
     pub tiles: HashMap<(i32, i32), Tile>,
@@ -29,6 +123,14 @@ impl BackgroundMap {
         }
     }
 
+    pub fn load_level(&mut self, level: &Level) {
+        self.tiles.clear();
+
+        for (position, tile) in level.background() {
+            self.tiles.insert(position, tile);
+        }
+    }
+
     pub fn generate(&mut self) {
         let mut rng = thread_rng();
         let tile_range = distributions::Uniform::new_inclusive(1, 100);
@@ -53,13 +155,54 @@ impl BackgroundMap {
                     _ => {}
                 }
 
-                let tile = Tile {
-                    sprite_layer: 0,
-                    sprite_id,
-                };
+                let tile = Tile::new(BACKGROUND_LAYER, "grass", sprite_id);
 
                 self.tiles.insert((x, y), tile);
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(duration: Duration) -> u64 {
+        duration.as_secs() * 1000 + u64::from(duration.subsec_nanos()) / 1_000_000
+    }
+
+    fn ticked(clock: &mut GameClock, ticks: u32) -> u64 {
+        for _ in 0..ticks {
+            clock.tick();
+        }
+
+        millis(clock.now())
+    }
+
+    #[test]
+    fn ticks_move_by_a_fixed_step() {
+        let mut clock = GameClock::new(Duration::from_millis(10));
+
+        assert_eq!(ticked(&mut clock, 12), 120);
+    }
+
+    #[test]
+    fn time_scale_speeds_the_clock_up() {
+        let mut clock = GameClock::new(Duration::from_millis(10));
+        clock.time_scale = 2.0;
+
+        assert_eq!(ticked(&mut clock, 5), 100);
+    }
+
+    #[test]
+    fn paused_clock_only_moves_by_stepped_frames() {
+        let mut clock = GameClock::new(Duration::from_millis(10));
+        clock.paused = true;
+
+        assert_eq!(ticked(&mut clock, 10), 0);
+
+        clock.step_frames(3);
+
+        assert_eq!(ticked(&mut clock, 10), 30);
+    }
+}
@@ -0,0 +1,673 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use ggez::graphics::{self, Color, DrawParam, Point2, TextCached, TextFragment};
+use ggez::timer;
+use ggez::{Context, GameResult};
+use specs::{Dispatcher, DispatcherBuilder, Entity, Join};
+use warmy;
+
+use ai::Behaviour;
+use animated_gif::AnimatedGif;
+use app::{SharedState, DESIRED_FPS};
+use assets::{self, Assets};
+use components;
+use entities;
+use environment::Environment;
+use goals::{Goals, LevelEvents};
+use history::History;
+use pathfinding::Pathfinder;
+use gui::{DialogEvent, GuiManager, Renderable, Widget, WidgetEvent};
+use input::{Buttons, ControllerState, PointerEvent};
+use level::{Level, LevelEntry};
+use resources;
+use screen::Screen;
+use scenes::level_complete::LevelCompleteScene;
+use scenes::pause::PauseScene;
+use scenes::{Scene, SceneSwitch};
+use scripting::{MapLayer, Script, ScriptCommand, ScriptEvent, ScriptEvents, ScriptHost};
+use species::SpeciesTable;
+use systems;
+use tilemap::{SpriteLayers, Tile, SHADOW_LAYER, TILE_SIZE};
+use tween::TweenState;
+use widgets::dialog::Dialog;
+use widgets::icon::AnimatedIcon;
+use widgets::panel::NineSlice;
+use world::World;
+
+pub struct GameplayScene {
+    level_entry: LevelEntry,
+    level: warmy::Res<Level>,
+    level_generation: usize,
+    level_name: String,
+    gui_manager: GuiManager,
+    stopwatch: AnimatedGif,
+    world: World,
+    sprite_layers: SpriteLayers,
+    dispatcher: Dispatcher<'static, 'static>,
+    dialog: Option<Box<Dialog>>,
+    dialog_speaker: Option<Entity>,
+    /// Lines waiting for the dialog to close: what the script said, and
+    /// anything that went wrong loading the level or reloading assets.
+    said: VecDeque<String>,
+    player: Entity,
+    script: Option<warmy::Res<Script>>,
+    script_generation: usize,
+    script_host: Option<ScriptHost>,
+    pause_requested: bool,
+}
+
+impl GameplayScene {
+    pub fn new(ctx: &mut Context, shared: &mut SharedState, level_entry: LevelEntry) -> GameResult<GameplayScene> {
+        let screen = shared.screen;
+        let assets = &mut shared.assets;
+        let controller_state = ControllerState::new();
+        let mut world = World::new();
+
+        let level_res = assets::get::<Level>(&mut assets.asset_store, &level_entry.path, ctx)?;
+        let level = level_res.borrow().clone();
+
+        let stopwatch = assets.animation("stopwatch", ctx)?;
+
+        let sprite_layers = SpriteLayers::new(screen);
+
+        let entity_map = resources::EntityMap::new();
+        let mut background_map = resources::BackgroundMap::new();
+
+        background_map.load_level(&level);
+
+        let mut environment = Environment::default();
+        environment.load_level(&level);
+
+        world.specs_world.add_resource(entity_map);
+        world.specs_world.add_resource(background_map);
+        world.specs_world.add_resource(environment);
+        world.specs_world.add_resource(controller_state);
+        world.specs_world.add_resource(resources::SoundEffects::default());
+        world.specs_world.add_resource(resources::TurnCounter::default());
+        world.specs_world.add_resource(History::default());
+        world.specs_world.add_resource(Pathfinder::default());
+        world.specs_world.add_resource(resources::PointerState::default());
+        world.specs_world.add_resource(resources::PathPreview::default());
+        world.specs_world.add_resource(Goals::new(level.objectives.clone(), level.par));
+        world.specs_world.add_resource(LevelEvents::default());
+        world.specs_world.add_resource(ScriptEvents {
+            queue: vec![ScriptEvent::Start],
+        });
+        world
+            .specs_world
+            .add_resource(resources::GameClock::new(timer::f64_to_duration(1.0 / DESIRED_FPS as f64)));
+
+        let (player_x, player_y) = level.player;
+        let player = entities::create_player(&mut world, player_x, player_y);
+
+        for creature in level.creatures.iter() {
+            entities::create_slime(&mut world, creature.x, creature.y, creature.behaviour.clone(), creature.hungry);
+        }
+
+        for trigger in level.triggers.iter() {
+            entities::create_trigger(&mut world, trigger.x, trigger.y, trigger.kind, trigger.channel);
+        }
+
+        for reactor in level.reactors.iter() {
+            entities::create_reactor(&mut world, reactor.x, reactor.y, reactor.kind, reactor.channel, reactor.inverted);
+        }
+
+        for block in level.blocks.iter() {
+            entities::create_block(&mut world, block.x, block.y, block.chain);
+        }
+
+        let species = assets.species.table();
+        let mut said = VecDeque::new();
+
+        for plant in level.plants.iter() {
+            match species.get(&plant.species) {
+                Some(kind) => {
+                    entities::create_plant(&mut world, plant.x, plant.y, kind);
+                }
+                None => said.push_back(format!("Level {}: unknown plant species {}", level.name, plant.species)),
+            }
+        }
+
+        world.specs_world.add_resource(species);
+
+        let dialog = match level.intro {
+            Some(ref intro) => Some(GameplayScene::build_dialog(ctx, assets, screen, intro, level.player)?),
+            None => None,
+        };
+
+        let script = match level.script {
+            Some(ref path) => Some(assets::get::<Script>(&mut assets.asset_store, path, ctx)?),
+            None => None,
+        };
+        let script_generation = script.as_ref().map_or(0, |script| script.borrow().generation);
+        let script_host = script
+            .as_ref()
+            .and_then(|script| GameplayScene::start_script(&level.name, &script.borrow(), &mut said));
+
+        let dialog_speaker = dialog.as_ref().map(|_| player);
+
+        let dispatcher = DispatcherBuilder::new()
+            .with(systems::AdvanceClock, "AdvanceClock", &[])
+            .with(systems::PlayerMovement { }, "PlayerMovement", &["AdvanceClock"])
+            .with(systems::UndoTurn, "UndoTurn", &["AdvanceClock"])
+            .with(systems::ClickToMove, "ClickToMove", &["PlayerMovement", "UndoTurn"])
+            // The player takes their turn, then the creatures take theirs,
+            // then the garden grows.
+            .with(systems::ProcessMovement { player_owned: true }, "PlayerTurn", &["ClickToMove"])
+            .with(systems::CreatureAi::default(), "CreatureAi", &["PlayerTurn"])
+            .with(systems::ProcessMovement { player_owned: false }, "CreatureTurn", &["CreatureAi"])
+            .with(systems::EatPlants, "EatPlants", &["CreatureTurn"])
+            .with(systems::SimulateEnvironment::default(), "SimulateEnvironment", &["EatPlants"])
+            .with(systems::Plantae::default(), "plantae", &["SimulateEnvironment"])
+            .with(systems::Mechanisms::default(), "Mechanisms", &["plantae"])
+            .with(systems::EvaluateGoals, "EvaluateGoals", &["Mechanisms"])
+            .with(systems::ProcessAnimation { }, "ProcessAnimation", &["AdvanceClock"])
+            .build();
+
+        Ok(GameplayScene {
+            level_entry,
+            level: level_res,
+            level_generation: level.generation,
+            level_name: level.name,
+            gui_manager: GameplayScene::build_hud(&screen, &stopwatch),
+            stopwatch,
+            world,
+            sprite_layers,
+            dispatcher,
+            dialog,
+            dialog_speaker,
+            said,
+            player,
+            script,
+            script_generation,
+            script_host,
+            pause_requested: false,
+        })
+    }
+
+    fn build_hud(screen: &Screen, stopwatch: &AnimatedGif) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = screen.to_screen_coordinates(Point2::new(
+            screen.logical_w as f32 - stopwatch.frame_width as f32 - 8.0,
+            8.0,
+        ));
+
+        gui_manager.widgets.push(AnimatedIcon::new(
+            stopwatch.clone(),
+            position,
+            Point2::new(screen.scale_w, screen.scale_h),
+        ));
+
+        gui_manager
+    }
+
+    fn build_dialog(
+        ctx: &mut Context,
+        assets: &Assets,
+        screen: Screen,
+        text: &str,
+        anchor: (i32, i32),
+    ) -> GameResult<Box<Dialog>> {
+        let scroll = assets.image("scroll", ctx)?;
+
+        Dialog::new(
+            ctx,
+            text,
+            NineSlice::new(scroll, 16),
+            assets.font.clone(),
+            assets.default_scale,
+            screen,
+            32,
+            anchor,
+        )
+    }
+
+    /// A broken script leaves the level playable without it. What went
+    /// wrong is shown in a dialog, since the game has no console to print to.
+    fn start_script(level_name: &str, script: &Script, said: &mut VecDeque<String>) -> Option<ScriptHost> {
+        match ScriptHost::new(&script.source) {
+            Ok(host) => Some(host),
+            Err(e) => {
+                said.push_back(format!("Level {}: script failed: {}", level_name, e));
+                None
+            }
+        }
+    }
+
+    /// Picks up edits to the level's script. The level isn't started over,
+    /// so the new script doesn't get a `start` event.
+    fn reload_script(&mut self) {
+        let script = match self.script {
+            Some(ref script) => script.borrow(),
+            None => return,
+        };
+
+        if script.generation == self.script_generation {
+            return;
+        }
+
+        self.script_generation = script.generation;
+        self.script_host = GameplayScene::start_script(&self.level_name, &script, &mut self.said);
+    }
+
+    /// Hands this frame's events to the script and carries out what it
+    /// asks for.
+    fn run_script(&mut self, shared: &SharedState) {
+        let events: Vec<ScriptEvent> = self.world
+            .specs_world
+            .write_resource::<ScriptEvents>()
+            .queue
+            .drain(..)
+            .collect();
+
+        let commands = {
+            let host = match self.script_host {
+                Some(ref mut host) => host,
+                None => return,
+            };
+
+            if events.is_empty() {
+                return;
+            }
+
+            host.sync_tiles(
+                &self.world.specs_world.read_resource::<resources::BackgroundMap>().tiles,
+                &self.world.specs_world.read_resource::<resources::EntityMap>().tiles,
+            );
+
+            for event in events.iter() {
+                if let Err(e) = host.handle(event) {
+                    self.said.push_back(format!(
+                        "Level {}: script failed on {}: {}",
+                        self.level_name,
+                        event.name(),
+                        e
+                    ));
+                }
+            }
+
+            host.take_commands()
+        };
+
+        for command in commands {
+            self.apply_script_command(shared, command);
+        }
+    }
+
+    fn apply_script_command(&mut self, shared: &SharedState, command: ScriptCommand) {
+        match command {
+            ScriptCommand::Spawn { kind, x, y } => match kind.as_str() {
+                "block" => {
+                    entities::create_block(&mut self.world, x, y, false);
+                }
+                "slime" => {
+                    entities::create_slime(&mut self.world, x, y, Behaviour::Wander, false);
+                }
+                species => match shared.assets.species.table().get(species) {
+                    Some(kind) => {
+                        entities::create_plant(&mut self.world, x, y, kind);
+                    }
+                    None => self
+                        .said
+                        .push_back(format!("Level {}: script can't spawn {}", self.level_name, species)),
+                },
+            },
+            ScriptCommand::SetTile { layer, position, tile } => match layer {
+                MapLayer::Background => {
+                    let mut background_map = self.world.specs_world.write_resource::<resources::BackgroundMap>();
+                    set_or_clear(&mut background_map.tiles, position, tile);
+                }
+                MapLayer::Entity => {
+                    let mut entity_map = self.world.specs_world.write_resource::<resources::EntityMap>();
+                    set_or_clear(&mut entity_map.tiles, position, tile);
+                }
+            },
+            ScriptCommand::Say { text } => self.said.push_back(text),
+        }
+    }
+
+    /// Opens the next line the script said once the player has read the
+    /// dialog before it.
+    fn show_said(&mut self, ctx: &mut Context, shared: &SharedState) -> GameResult<()> {
+        if self.dialog.is_some() {
+            return Ok(());
+        }
+
+        let text = match self.said.pop_front() {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+
+        let anchor = match self.world.specs_world.read_storage::<components::Position>().get(self.player) {
+            Some(position) => (position.x, position.y),
+            None => (0, 0),
+        };
+
+        self.dialog = Some(GameplayScene::build_dialog(ctx, &shared.assets, shared.screen, &text, anchor)?);
+        self.dialog_speaker = Some(self.player);
+
+        Ok(())
+    }
+
+    /// Picks up edits to the level file: the layout is swapped in place,
+    /// while entities stay where they are.
+    fn reload_level(&mut self) {
+        let level = self.level.borrow();
+
+        if level.generation == self.level_generation {
+            return;
+        }
+
+        self.level_generation = level.generation;
+        self.level_name = level.name.clone();
+
+        let mut background_map = self.world.specs_world.write_resource::<resources::BackgroundMap>();
+        background_map.load_level(&level);
+
+        let mut environment = self.world.specs_world.write_resource::<Environment>();
+        environment.load_level(&level);
+
+        let mut goals = self.world.specs_world.write_resource::<Goals>();
+        *goals = Goals::new(level.objectives.clone(), level.par);
+    }
+
+    /// Hands edits to the species file to the world; the `Plantae` system
+    /// updates the plants from them.
+    fn reload_species(&mut self, shared: &SharedState) {
+        let generation = shared.assets.species.generation();
+        let mut species = self.world.specs_world.write_resource::<SpeciesTable>();
+
+        if species.generation != generation {
+            *species = shared.assets.species.table();
+        }
+    }
+
+    fn update_dialog(&mut self, dt: Duration) {
+        if let Some(ref mut dialog) = self.dialog {
+            if let Some(speaker) = self.dialog_speaker {
+                let positions = self.world.specs_world.read_storage::<components::Position>();
+
+                if let Some(position) = positions.get(speaker) {
+                    dialog.set_anchor(position.x, position.y);
+                }
+            }
+
+            dialog.update(dt);
+        }
+    }
+
+    fn interact_dialog(&mut self, button: Buttons) {
+        let mut messages = VecDeque::new();
+
+        if let Some(ref mut dialog) = self.dialog {
+            dialog.interact(button, &mut messages);
+        }
+
+        for message in messages {
+            if let WidgetEvent::DialogEvent(DialogEvent::Finished) = message {
+                self.dialog = None;
+                self.dialog_speaker = None;
+            }
+        }
+    }
+}
+
+fn set_or_clear(tiles: &mut HashMap<(i32, i32), Tile>, position: (i32, i32), tile: Option<Tile>) {
+    match tile {
+        Some(tile) => {
+            tiles.insert(position, tile);
+        }
+        None => {
+            tiles.remove(&position);
+        }
+    }
+}
+
+/// Combines the tweens of an entity's animations that are playing at `now`.
+fn animation_state(animation: &components::Animation, now: Duration) -> TweenState {
+    let mut state = TweenState::default();
+
+    for event in animation.animation_queue.iter() {
+        match *event {
+            components::AnimationEvent::Position(ref position) => state.apply(&position.tween(), now),
+            components::AnimationEvent::Tween(ref tween) => state.apply(tween, now),
+        }
+    }
+
+    state
+}
+
+impl Scene for GameplayScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        if self.pause_requested {
+            self.pause_requested = false;
+
+            let pause = PauseScene::new(ctx, shared, self.level_entry.clone())?;
+            return Ok(SceneSwitch::Push(Box::new(pause)));
+        }
+
+        let dt = timer::f64_to_duration(1.0 / DESIRED_FPS as f64);
+
+        self.reload_level();
+        self.reload_species(shared);
+        self.reload_script();
+        self.dispatcher.dispatch(&self.world.specs_world.res);
+        self.world.specs_world.maintain();
+        self.run_script(shared);
+        self.said.extend(shared.assets.errors.drain(..));
+        self.show_said(ctx, shared)?;
+
+        {
+            let mut sound_effects = self.world.specs_world.write_resource::<resources::SoundEffects>();
+
+            for effect in sound_effects.queue.drain(..) {
+                shared.audio.play(effect);
+            }
+        }
+
+        let level_event = self.world.specs_world.write_resource::<LevelEvents>().queue.drain(..).next();
+
+        if let Some(level_event) = level_event {
+            let par = self.world.specs_world.read_resource::<Goals>().par;
+            let results = LevelCompleteScene::new(ctx, shared, self.level_entry.clone(), level_event, par)?;
+
+            return Ok(SceneSwitch::Push(Box::new(results)));
+        }
+
+        self.gui_manager.update(dt);
+        self.update_dialog(dt);
+
+        {
+            let mut controller_state = self.world.specs_world.write_resource::<ControllerState>();
+            controller_state.update();
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 1.0))?;
+
+        let now = self.world.specs_world.read_resource::<resources::GameClock>().now();
+
+        let background_map = self.world
+            .specs_world
+            .read_resource::<resources::BackgroundMap>();
+
+        let entity_map = self.world
+            .specs_world
+            .read_resource::<resources::EntityMap>();
+
+        let tilesets = &shared.assets.tilesets;
+        let still = TweenState::default();
+
+        for ((x, y), tile) in background_map.tiles.iter() {
+            self.sprite_layers.add(tilesets, tile, *x, *y, &still, 0);
+        }
+
+        for ((x, y), tile) in entity_map.tiles.iter() {
+            self.sprite_layers.add(tilesets, tile, *x, *y, &still, 0);
+        }
+
+        let highlight = Tile::new(SHADOW_LAYER, "active-tile", 0);
+
+        for &(x, y) in self.world.specs_world.read_resource::<resources::PathPreview>().tiles.iter() {
+            self.sprite_layers.add(tilesets, &highlight, x, y, &still, 0);
+        }
+
+        let positions = self.world.specs_world.read_storage::<components::Position>();
+        let sprites = self.world.specs_world.read_storage::<components::Sprite>();
+        let animations = self.world.specs_world.read_storage::<components::Animation>();
+        let shadows = self.world.specs_world.read_storage::<components::Shadow>();
+        let shadow = Tile::new(SHADOW_LAYER, "shadow", 0);
+
+        for (position, animation, _) in (&positions, &animations, &shadows).join() {
+            let state = animation_state(animation, now);
+            // Shadows stay flat on the ground, they only follow the entity around.
+            let shadow_state = TweenState {
+                offset: state.offset,
+                scale: state.scale,
+                ..TweenState::default()
+            };
+
+            self.sprite_layers.add(tilesets, &shadow, position.x, position.y, &shadow_state, 0);
+        }
+
+        for (position, sprite, animation) in (&positions, &sprites, &animations).join() {
+            let state = animation_state(animation, now);
+
+            self.sprite_layers.add(tilesets, &sprite.tile, position.x, position.y, &state, sprite.z);
+        }
+
+        self.sprite_layers.draw(ctx)?;
+
+        let fps = timer::get_fps(ctx);
+        let fps_display = TextCached::new(TextFragment {
+            text: format!("FPS: {}", fps),
+            font_id: Some(shared.assets.font.clone().into()),
+            scale: Some(shared.assets.default_scale),
+            ..Default::default()
+        })?;
+
+        fps_display.queue(
+            ctx,
+            shared.screen.to_screen_coordinates(Point2::new(5.0, 0.0)),
+            None,
+        );
+
+        let moves = self.world.specs_world.read_resource::<resources::TurnCounter>().moves;
+        let moves_text = match self.world.specs_world.read_resource::<Goals>().par {
+            Some(par) => format!("Moves: {} / Par: {}", moves, par),
+            None => format!("Moves: {}", moves),
+        };
+        let moves_display = TextCached::new(TextFragment {
+            text: moves_text,
+            font_id: Some(shared.assets.font.clone().into()),
+            scale: Some(shared.assets.default_scale),
+            ..Default::default()
+        })?;
+
+        moves_display.queue(
+            ctx,
+            shared.screen.to_screen_coordinates(Point2::new(5.0, 20.0)),
+            None,
+        );
+
+        let logo = TextCached::new(TextFragment {
+            text: self.level_name.clone(),
+            font_id: Some(shared.assets.font.clone().into()),
+            scale: Some(shared.assets.default_scale),
+            ..Default::default()
+        })?;
+
+        let position = Point2::new(
+            (shared.screen.logical_w as f32 / 2.0) * shared.screen.scale_w - (logo.width(ctx) as f32 / 2.0),
+            (shared.screen.logical_h as f32 - 25.0) * shared.screen.scale_h
+        );
+
+        logo.queue(
+            ctx,
+            position,
+            None,
+        );
+
+        TextCached::draw_queued(ctx, DrawParam::default())?;
+
+        self.gui_manager.render(ctx)?;
+
+        if let Some(ref dialog) = self.dialog {
+            dialog.render(ctx)?;
+        }
+
+        Ok(())
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.sprite_layers.set_screen(shared.screen);
+        self.gui_manager = GameplayScene::build_hud(&shared.screen, &self.stopwatch);
+
+        if let Some(ref mut dialog) = self.dialog {
+            dialog.resize(shared.screen, shared.assets.default_scale);
+        }
+
+        Ok(())
+    }
+
+    /// Game time stands still under the pause menu and the results, so
+    /// nothing has moved on when the player gets back to the level.
+    fn covered(&mut self) {
+        self.world.specs_world.write_resource::<resources::GameClock>().paused = true;
+    }
+
+    fn uncovered(&mut self) {
+        self.world.specs_world.write_resource::<resources::GameClock>().paused = false;
+    }
+
+    fn music(&self) -> Option<&'static str> {
+        Some("garden")
+    }
+
+    fn input(&mut self, _shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if pressed && button == Buttons::Cancel {
+            self.pause_requested = true;
+            return;
+        }
+
+        if self.dialog.is_some() {
+            if pressed {
+                self.interact_dialog(button);
+            }
+
+            return;
+        }
+
+        let mut controller_state = self.world.specs_world.write_resource::<ControllerState>();
+
+        if pressed {
+            controller_state.button_down(button);
+        } else {
+            controller_state.button_up(button);
+        }
+    }
+
+    fn pointer(&mut self, _shared: &mut SharedState, event: PointerEvent) {
+        if self.dialog.is_some() {
+            return;
+        }
+
+        let to_tile = |position: Point2| {
+            (
+                (position.x / TILE_SIZE as f32).floor() as i32,
+                (position.y / TILE_SIZE as f32).floor() as i32,
+            )
+        };
+
+        let mut pointer = self.world.specs_world.write_resource::<resources::PointerState>();
+
+        match event {
+            PointerEvent::Moved(position) => pointer.hovered = Some(to_tile(position)),
+            PointerEvent::Clicked(position) => pointer.clicked = Some(to_tile(position)),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use goals::{LevelEvent, Limit};
+use gui::{GuiManager, MenuEvent, WidgetEvent};
+use input::Buttons;
+use level::LevelEntry;
+use scenes::gameplay::GameplayScene;
+use scenes::level_select::{self, LevelSelectScene};
+use scenes::{self, Scene, SceneSwitch};
+use widgets::menu::Menu;
+
+enum CompleteOption {
+    NextLevel(LevelEntry),
+    Retry,
+    LevelSelect,
+}
+
+/// Results shown over the level when its goals are met, or when a limit was
+/// broken and it has to be retried.
+pub struct LevelCompleteScene {
+    level_entry: LevelEntry,
+    result: LevelEvent,
+    par: Option<u32>,
+    options: Vec<CompleteOption>,
+    gui_manager: GuiManager,
+    events: VecDeque<WidgetEvent>,
+}
+
+impl LevelCompleteScene {
+    pub fn new(
+        ctx: &mut Context,
+        shared: &mut SharedState,
+        level_entry: LevelEntry,
+        result: LevelEvent,
+        par: Option<u32>,
+    ) -> GameResult<LevelCompleteScene> {
+        let levels = level_select::load_levels(ctx, shared)?;
+        let next_level = levels
+            .iter()
+            .position(|level| level.path == level_entry.path)
+            .and_then(|index| levels.get(index + 1))
+            .cloned();
+
+        let mut options = Vec::new();
+
+        if let (Some(next_level), LevelEvent::Complete { .. }) = (next_level, result) {
+            options.push(CompleteOption::NextLevel(next_level));
+        }
+
+        options.push(CompleteOption::Retry);
+        options.push(CompleteOption::LevelSelect);
+
+        scenes::reset_menu_selection(shared);
+
+        Ok(LevelCompleteScene {
+            level_entry,
+            result,
+            par,
+            gui_manager: LevelCompleteScene::build_gui(shared, &options),
+            options,
+            events: VecDeque::new(),
+        })
+    }
+
+    fn build_gui(shared: &SharedState, options: &[CompleteOption]) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = scenes::menu_position(shared);
+
+        let mut menu = Menu::new(
+            position.x,
+            position.y,
+            scenes::menu_spacing(shared),
+            shared.assets.font.clone(),
+            shared.assets.default_scale,
+            shared.store.clone(),
+        );
+
+        for option in options.iter() {
+            menu = match *option {
+                CompleteOption::NextLevel(_) => menu.with_option("Next Level"),
+                CompleteOption::Retry => menu.with_option("Retry"),
+                CompleteOption::LevelSelect => menu.with_option("Level Select"),
+            };
+        }
+
+        gui_manager.widgets.push(menu);
+
+        gui_manager
+    }
+}
+
+impl Scene for LevelCompleteScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        while let Some(event) = self.events.pop_front() {
+            if let WidgetEvent::MenuEvent(MenuEvent::Selected(index)) = event {
+                let scene: Box<Scene> = match self.options.get(index as usize) {
+                    Some(&CompleteOption::NextLevel(ref next_level)) => {
+                        Box::new(GameplayScene::new(ctx, shared, next_level.clone())?)
+                    }
+                    Some(&CompleteOption::Retry) => {
+                        Box::new(GameplayScene::new(ctx, shared, self.level_entry.clone())?)
+                    }
+                    Some(&CompleteOption::LevelSelect) => Box::new(LevelSelectScene::new(ctx, shared)?),
+                    None => continue,
+                };
+
+                return Ok(SceneSwitch::Reset(scene));
+            }
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        scenes::draw_overlay(ctx, shared, 0.6)?;
+
+        match self.result {
+            LevelEvent::Complete { moves, stars } => {
+                let moves = match self.par {
+                    Some(par) => format!("{} moves, par {}", moves, par),
+                    None => format!("{} moves", moves),
+                };
+
+                scenes::draw_heading(ctx, shared, "Level complete!", 112.0, 48.0)?;
+                scenes::draw_heading(ctx, shared, &star_text(stars), 176.0, 32.0)?;
+                scenes::draw_heading(ctx, shared, &moves, 216.0, 24.0)?;
+            }
+            LevelEvent::Failed { limit } => {
+                let heading = match limit {
+                    Limit::Moves => "Out of moves",
+                    Limit::Turns => "Out of turns",
+                };
+
+                scenes::draw_heading(ctx, shared, heading, 160.0, 48.0)?;
+            }
+        }
+
+        self.gui_manager.render(ctx)
+    }
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if pressed {
+            let events = self.gui_manager.interact(button);
+            scenes::play_menu_sounds(shared, &events);
+            self.events.extend(events);
+        }
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.gui_manager = LevelCompleteScene::build_gui(shared, &self.options);
+        Ok(())
+    }
+
+    fn draw_previous(&self) -> bool {
+        true
+    }
+}
+
+/// Filled and empty stars out of three, in characters the UI font has.
+fn star_text(stars: u32) -> String {
+    (0..3).map(|star| if star < stars { "*" } else { "-" }).collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use assets;
+use gui::{GuiManager, MenuEvent, WidgetEvent};
+use input::Buttons;
+use level::{LevelEntry, LevelIndex, LEVEL_INDEX_PATH};
+use scenes::gameplay::GameplayScene;
+use scenes::title::TitleScene;
+use scenes::{self, Scene, SceneSwitch};
+use widgets::menu::Menu;
+
+pub struct LevelSelectScene {
+    levels: Vec<LevelEntry>,
+    gui_manager: GuiManager,
+    events: VecDeque<WidgetEvent>,
+}
+
+impl LevelSelectScene {
+    pub fn new(ctx: &mut Context, shared: &mut SharedState) -> GameResult<LevelSelectScene> {
+        let levels = load_levels(ctx, shared)?;
+
+        scenes::reset_menu_selection(shared);
+
+        Ok(LevelSelectScene {
+            gui_manager: LevelSelectScene::build_gui(shared, &levels),
+            levels,
+            events: VecDeque::new(),
+        })
+    }
+
+    fn build_gui(shared: &SharedState, levels: &[LevelEntry]) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = scenes::menu_position(shared);
+
+        let mut menu = Menu::new(
+            position.x,
+            position.y,
+            scenes::menu_spacing(shared),
+            shared.assets.font.clone(),
+            shared.assets.default_scale,
+            shared.store.clone(),
+        );
+
+        for level in levels.iter() {
+            menu = menu.with_option(&level.name);
+        }
+
+        gui_manager.widgets.push(menu.with_option("Back"));
+
+        gui_manager
+    }
+}
+
+impl Scene for LevelSelectScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        while let Some(event) = self.events.pop_front() {
+            if let WidgetEvent::MenuEvent(MenuEvent::Selected(index)) = event {
+                let scene_switch = match self.levels.get(index as usize) {
+                    Some(level) => {
+                        let gameplay = GameplayScene::new(ctx, shared, level.clone())?;
+                        SceneSwitch::Replace(Box::new(gameplay))
+                    }
+                    None => {
+                        let title = TitleScene::new(ctx, shared)?;
+                        SceneSwitch::Replace(Box::new(title))
+                    }
+                };
+
+                return Ok(scene_switch);
+            }
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        scenes::draw_heading(ctx, shared, "Select a level", 160.0, 48.0)?;
+        self.gui_manager.render(ctx)
+    }
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if pressed {
+            let events = self.gui_manager.interact(button);
+            scenes::play_menu_sounds(shared, &events);
+            self.events.extend(events);
+        }
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.gui_manager = LevelSelectScene::build_gui(shared, &self.levels);
+        Ok(())
+    }
+
+    fn music(&self) -> Option<&'static str> {
+        Some("title")
+    }
+}
+
+pub fn load_levels(ctx: &mut Context, shared: &mut SharedState) -> GameResult<Vec<LevelEntry>> {
+    let index = assets::get::<LevelIndex>(&mut shared.assets.asset_store, LEVEL_INDEX_PATH, ctx)?;
+
+    let levels = index.borrow().levels.clone();
+
+    Ok(levels)
+}
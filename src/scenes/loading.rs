@@ -0,0 +1,101 @@
+use ggez::graphics::{self, Color, DrawMode, Rect};
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use input::Buttons;
+use manifest::Preloader;
+use scenes::title::TitleScene;
+use scenes::{self, Scene, SceneSwitch};
+
+const BAR_WIDTH: f32 = 320.0;
+const BAR_HEIGHT: f32 = 16.0;
+
+/// Shown at startup while the `Preloader` works through the manifest. If
+/// anything failed to load, lists it and waits for a key press before going
+/// on to the title with placeholders.
+pub struct LoadingScene {
+    preloader: Preloader,
+    /// Only load the next asset once the progress so far has been drawn,
+    /// otherwise the update loop catching up would load everything in one
+    /// frame.
+    drawn: bool,
+    continue_requested: bool,
+}
+
+impl LoadingScene {
+    pub fn new(ctx: &mut Context, shared: &mut SharedState) -> GameResult<LoadingScene> {
+        Ok(LoadingScene {
+            preloader: Preloader::new(&mut shared.assets, ctx)?,
+            drawn: false,
+            continue_requested: false,
+        })
+    }
+
+    fn draw_progress_bar(&self, ctx: &mut Context, shared: &SharedState) -> GameResult<()> {
+        let screen = &shared.screen;
+        let x = (screen.logical_w as f32 - BAR_WIDTH) / 2.0;
+        let y = screen.logical_h as f32 / 2.0;
+        let outline = Rect::new(
+            x * screen.scale_w + screen.offset_x,
+            y * screen.scale_h + screen.offset_y,
+            BAR_WIDTH * screen.scale_w,
+            BAR_HEIGHT * screen.scale_h,
+        );
+        let filled = Rect::new(outline.x, outline.y, outline.w * self.preloader.progress(), outline.h);
+
+        graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 1.0))?;
+        graphics::rectangle(ctx, DrawMode::Fill, filled)?;
+        graphics::rectangle(ctx, DrawMode::Line(2.0), outline)
+    }
+}
+
+impl Scene for LoadingScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        if !self.preloader.is_done() {
+            if self.drawn {
+                self.drawn = false;
+                self.preloader.step(&mut shared.assets, ctx);
+            }
+
+            return Ok(SceneSwitch::None);
+        }
+
+        if self.preloader.errors.is_empty() || self.continue_requested {
+            let title = TitleScene::new(ctx, shared)?;
+            return Ok(SceneSwitch::Replace(Box::new(title)));
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.drawn = true;
+
+        if !self.preloader.is_done() || self.preloader.errors.is_empty() {
+            scenes::draw_heading(ctx, shared, "Loading", 160.0, 48.0)?;
+
+            if let Some(current) = self.preloader.current() {
+                scenes::draw_heading(ctx, shared, current, shared.screen.logical_h as f32 / 2.0 + 32.0, 24.0)?;
+            }
+
+            return self.draw_progress_bar(ctx, shared);
+        }
+
+        scenes::draw_heading(ctx, shared, "Some assets failed to load", 64.0, 32.0)?;
+
+        let mut y = 112.0;
+
+        for error in self.preloader.errors.iter() {
+            scenes::draw_heading(ctx, shared, error, y, 16.0)?;
+            y += 20.0;
+        }
+
+        scenes::draw_heading(ctx, shared, "Press action to continue", y + 24.0, 24.0)
+    }
+
+    fn input(&mut self, _shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if pressed && button == Buttons::Action && self.preloader.is_done() {
+            self.continue_requested = true;
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use ggez::graphics::{self, Color, DrawMode, DrawParam, Point2, Rect, TextCached, TextFragment};
+use ggez::{Context, GameResult};
+
+use std::collections::VecDeque;
+
+use app::SharedState;
+use assets::Assets;
+use audio::SoundEffect;
+use gui::{MenuEvent, WidgetEvent};
+use input::{Buttons, PointerEvent};
+use state::{Action, MenuAction};
+
+pub mod gameplay;
+pub mod level_complete;
+pub mod level_select;
+pub mod loading;
+pub mod options;
+pub mod pause;
+pub mod title;
+
+pub enum SceneSwitch {
+    None,
+    Push(Box<Scene>),
+    Pop,
+    Replace(Box<Scene>),
+    /// Clears the whole stack before pushing the new scene, e.g. when
+    /// quitting from the pause menu back to the title.
+    Reset(Box<Scene>),
+}
+
+pub trait Scene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch>;
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()>;
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool);
+
+    /// Mouse input; most scenes are played with the keyboard alone.
+    fn pointer(&mut self, _shared: &mut SharedState, _event: PointerEvent) {}
+
+    /// Called on every scene in the stack after `SharedState::screen` changed.
+    fn resize(&mut self, _ctx: &mut Context, _shared: &mut SharedState) -> GameResult<()> {
+        Ok(())
+    }
+
+    /// Called when another scene is pushed on top of this one.
+    fn covered(&mut self) {}
+
+    /// Called when this scene is back on top after the one above it left.
+    fn uncovered(&mut self) {}
+
+    /// Whether the scene beneath this one should be drawn first, for
+    /// overlays such as the pause menu.
+    fn draw_previous(&self) -> bool {
+        false
+    }
+
+    /// The music track to play while this scene is on top, or `None` to
+    /// keep whatever is playing.
+    fn music(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Only the top scene is updated and receives input; drawing starts at the
+/// lowest scene that is visible through the overlays above it.
+pub struct SceneStack {
+    scenes: Vec<Box<Scene>>,
+}
+
+impl SceneStack {
+    pub fn new(initial_scene: Box<Scene>) -> SceneStack {
+        SceneStack {
+            scenes: vec![initial_scene],
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn push(&mut self, scene: Box<Scene>) {
+        if let Some(top) = self.scenes.last_mut() {
+            top.covered();
+        }
+
+        self.scenes.push(scene);
+    }
+
+    pub fn pop(&mut self) -> Option<Box<Scene>> {
+        let scene = self.scenes.pop();
+
+        if let Some(top) = self.scenes.last_mut() {
+            top.uncovered();
+        }
+
+        scene
+    }
+
+    pub fn switch(&mut self, scene_switch: SceneSwitch) {
+        match scene_switch {
+            SceneSwitch::None => {}
+            SceneSwitch::Push(scene) => {
+                self.push(scene);
+            }
+            SceneSwitch::Pop => {
+                self.pop();
+            }
+            SceneSwitch::Replace(scene) => {
+                self.pop();
+                self.push(scene);
+            }
+            SceneSwitch::Reset(scene) => {
+                self.scenes.clear();
+                self.push(scene);
+            }
+        }
+    }
+
+    pub fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        let scene_switch = match self.scenes.last_mut() {
+            Some(scene) => scene.update(ctx, shared)?,
+            None => SceneSwitch::None,
+        };
+
+        self.switch(scene_switch);
+
+        Ok(())
+    }
+
+    pub fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        let mut first_visible = self.scenes.len().saturating_sub(1);
+
+        while first_visible > 0 && self.scenes[first_visible].draw_previous() {
+            first_visible -= 1;
+        }
+
+        for scene in self.scenes[first_visible..].iter_mut() {
+            scene.draw(ctx, shared)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn resize(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        for scene in self.scenes.iter_mut() {
+            scene.resize(ctx, shared)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn music(&self) -> Option<&'static str> {
+        self.scenes.last().and_then(|scene| scene.music())
+    }
+
+    pub fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.input(shared, button, pressed);
+        }
+    }
+
+    pub fn pointer(&mut self, shared: &mut SharedState, event: PointerEvent) {
+        if let Some(scene) = self.scenes.last_mut() {
+            scene.pointer(shared, event);
+        }
+    }
+}
+
+/// Draws a line of text horizontally centered at logical height `y`.
+pub fn draw_heading(ctx: &mut Context, shared: &SharedState, text: &str, y: f32, pixels: f32) -> GameResult<()> {
+    let screen = &shared.screen;
+    let heading = TextCached::new(TextFragment {
+        text: text.to_string(),
+        font_id: Some(shared.assets.font.clone().into()),
+        scale: Some(Assets::display_independent_scale(screen.scale_w, screen.scale_h, pixels)),
+        ..Default::default()
+    })?;
+
+    let position = Point2::new(
+        (screen.logical_w as f32 / 2.0) * screen.scale_w - (heading.width(ctx) as f32 / 2.0),
+        y * screen.scale_h,
+    );
+
+    heading.queue(ctx, position, Some(Color::new(1.0, 1.0, 1.0, 1.0)));
+
+    TextCached::draw_queued(ctx, DrawParam::default())
+}
+
+/// Dims everything drawn so far, used by overlay scenes.
+pub fn draw_overlay(ctx: &mut Context, shared: &SharedState, alpha: f32) -> GameResult<()> {
+    let screen = &shared.screen;
+
+    graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, alpha))?;
+    graphics::rectangle(
+        ctx,
+        DrawMode::Fill,
+        Rect::new(0.0, 0.0, screen.screen_w as f32, screen.screen_h as f32),
+    )?;
+    graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 1.0))
+}
+
+/// Where menus are placed on screen, in screen coordinates.
+pub fn menu_position(shared: &SharedState) -> Point2 {
+    let screen = &shared.screen;
+
+    screen.to_screen_coordinates(Point2::new(
+        screen.logical_w as f32 / 2.0 - 96.0,
+        screen.logical_h as f32 / 2.0,
+    ))
+}
+
+pub fn menu_spacing(shared: &SharedState) -> f32 {
+    32.0 * shared.screen.scale_h
+}
+
+/// Menus share the selected index in the `Store`, so a scene showing a new
+/// menu starts it from the top.
+pub fn reset_menu_selection(shared: &SharedState) {
+    shared
+        .store
+        .borrow_mut()
+        .dispatch(Action::Menus(MenuAction::SetActiveMenuItemIndex(0)));
+}
+
+/// Plays the menu sounds for whatever the menu just did.
+pub fn play_menu_sounds(shared: &mut SharedState, events: &VecDeque<WidgetEvent>) {
+    for event in events.iter() {
+        match *event {
+            WidgetEvent::MenuEvent(MenuEvent::Moved(_)) => shared.audio.play(SoundEffect::MenuMove),
+            WidgetEvent::MenuEvent(MenuEvent::Selected(_)) => shared.audio.play(SoundEffect::MenuSelect),
+            _ => {}
+        }
+    }
+}
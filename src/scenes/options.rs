@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use audio::SoundEffect;
+use gui::{GuiManager, MenuEvent, WidgetEvent};
+use input::Buttons;
+use scenes::{self, Scene, SceneSwitch};
+use settings::{step_volume, Settings};
+use widgets::menu::Menu;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum OptionRow {
+    Resolution,
+    DisplayMode,
+    VSync,
+    PixelPerfect,
+    TextScale,
+    MusicVolume,
+    SfxVolume,
+    Back,
+}
+
+const ROWS: [OptionRow; 8] = [
+    OptionRow::Resolution,
+    OptionRow::DisplayMode,
+    OptionRow::VSync,
+    OptionRow::PixelPerfect,
+    OptionRow::TextScale,
+    OptionRow::MusicVolume,
+    OptionRow::SfxVolume,
+    OptionRow::Back,
+];
+
+impl OptionRow {
+    fn label(&self, settings: &Settings) -> String {
+        match *self {
+            OptionRow::Resolution => format!("Resolution: {}x{}", settings.width, settings.height),
+            OptionRow::DisplayMode => format!("Display: {}", settings.display_mode.name()),
+            OptionRow::VSync => format!("VSync: {}", on_off(settings.vsync)),
+            OptionRow::PixelPerfect => format!("Pixel Perfect: {}", on_off(settings.pixel_perfect)),
+            OptionRow::TextScale => format!("Text Scale: {}%", (settings.ui_scale * 100.0).round()),
+            OptionRow::MusicVolume => format!("Music: {}%", (settings.music_volume * 100.0).round()),
+            OptionRow::SfxVolume => format!("Sound: {}%", (settings.sfx_volume * 100.0).round()),
+            OptionRow::Back => "Back".to_string(),
+        }
+    }
+
+    /// Steps the setting for this row, returning whether anything changed.
+    fn change(&self, settings: &mut Settings, step: i32) -> bool {
+        match *self {
+            OptionRow::Resolution => settings.cycle_resolution(step),
+            OptionRow::DisplayMode => settings.display_mode = settings.display_mode.cycle(step),
+            OptionRow::VSync => settings.vsync = !settings.vsync,
+            OptionRow::PixelPerfect => settings.pixel_perfect = !settings.pixel_perfect,
+            OptionRow::TextScale => settings.cycle_ui_scale(step),
+            OptionRow::MusicVolume => settings.music_volume = step_volume(settings.music_volume, step),
+            OptionRow::SfxVolume => settings.sfx_volume = step_volume(settings.sfx_volume, step),
+            OptionRow::Back => return false,
+        }
+
+        true
+    }
+
+    /// Volume changes don't need the window rebuilt.
+    fn is_audio(&self) -> bool {
+        *self == OptionRow::MusicVolume || *self == OptionRow::SfxVolume
+    }
+}
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+/// Display and audio settings. Changes are applied as soon as they're made and written
+/// to the settings file when leaving the menu.
+pub struct OptionsScene {
+    gui_manager: GuiManager,
+    back_requested: bool,
+}
+
+impl OptionsScene {
+    pub fn new(_ctx: &mut Context, shared: &mut SharedState) -> GameResult<OptionsScene> {
+        scenes::reset_menu_selection(shared);
+
+        Ok(OptionsScene {
+            gui_manager: OptionsScene::build_gui(shared),
+            back_requested: false,
+        })
+    }
+
+    fn build_gui(shared: &SharedState) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = scenes::menu_position(shared);
+
+        let menu = ROWS.iter().fold(
+            Menu::new(
+                position.x,
+                position.y,
+                scenes::menu_spacing(shared),
+                shared.assets.font.clone(),
+                shared.assets.default_scale,
+                shared.store.clone(),
+            ),
+            |menu, row| menu.with_option(&row.label(&shared.settings)),
+        );
+
+        gui_manager.widgets.push(menu);
+
+        gui_manager
+    }
+
+    fn change(&mut self, shared: &mut SharedState, index: i32, step: i32) {
+        let row = match ROWS.get(index as usize) {
+            Some(row) => *row,
+            None => return,
+        };
+
+        if row == OptionRow::Back {
+            self.back_requested = true;
+        } else if row.change(&mut shared.settings, step) {
+            if row.is_audio() {
+                shared.audio.apply_settings(&shared.settings);
+            } else {
+                shared.settings_changed = true;
+            }
+
+            shared.audio.play(SoundEffect::MenuMove);
+            self.gui_manager = OptionsScene::build_gui(shared);
+        }
+    }
+}
+
+impl Scene for OptionsScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        if self.back_requested {
+            shared.settings.save(&mut ctx.filesystem)?;
+            scenes::reset_menu_selection(shared);
+
+            return Ok(SceneSwitch::Pop);
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        scenes::draw_heading(ctx, shared, "Options", 160.0, 48.0)?;
+        self.gui_manager.render(ctx)
+    }
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if !pressed {
+            return;
+        }
+
+        let selected_index = shared.store.borrow().selected_menu_item_index;
+
+        match button {
+            Buttons::Cancel => self.back_requested = true,
+            Buttons::Left => self.change(shared, selected_index, -1),
+            Buttons::Right => self.change(shared, selected_index, 1),
+            _ => {
+                let events = self.gui_manager.interact(button);
+                scenes::play_menu_sounds(shared, &events);
+
+                for event in events {
+                    if let WidgetEvent::MenuEvent(MenuEvent::Selected(index)) = event {
+                        self.change(shared, index, 1);
+                    }
+                }
+            }
+        }
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.gui_manager = OptionsScene::build_gui(shared);
+        Ok(())
+    }
+}
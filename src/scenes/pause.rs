@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use gui::{GuiManager, MenuEvent, WidgetEvent};
+use input::Buttons;
+use level::LevelEntry;
+use scenes::gameplay::GameplayScene;
+use scenes::level_select::LevelSelectScene;
+use scenes::options::OptionsScene;
+use scenes::title::TitleScene;
+use scenes::{self, Scene, SceneSwitch};
+use widgets::menu::Menu;
+
+const MENU_RESUME: i32 = 0;
+const MENU_RESTART: i32 = 1;
+const MENU_OPTIONS: i32 = 2;
+const MENU_LEVEL_SELECT: i32 = 3;
+const MENU_QUIT_TO_TITLE: i32 = 4;
+
+pub struct PauseScene {
+    level_entry: LevelEntry,
+    gui_manager: GuiManager,
+    events: VecDeque<WidgetEvent>,
+    resume_requested: bool,
+}
+
+impl PauseScene {
+    pub fn new(_ctx: &mut Context, shared: &mut SharedState, level_entry: LevelEntry) -> GameResult<PauseScene> {
+        scenes::reset_menu_selection(shared);
+
+        Ok(PauseScene {
+            level_entry,
+            gui_manager: PauseScene::build_gui(shared),
+            events: VecDeque::new(),
+            resume_requested: false,
+        })
+    }
+
+    fn build_gui(shared: &SharedState) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = scenes::menu_position(shared);
+
+        let menu = Menu::new(
+            position.x,
+            position.y,
+            scenes::menu_spacing(shared),
+            shared.assets.font.clone(),
+            shared.assets.default_scale,
+            shared.store.clone(),
+        ).with_option("Resume")
+            .with_option("Restart")
+            .with_option("Options")
+            .with_option("Level Select")
+            .with_option("Quit to Title");
+
+        gui_manager.widgets.push(menu);
+
+        gui_manager
+    }
+}
+
+impl Scene for PauseScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        if self.resume_requested {
+            return Ok(SceneSwitch::Pop);
+        }
+
+        while let Some(event) = self.events.pop_front() {
+            if let WidgetEvent::MenuEvent(MenuEvent::Selected(index)) = event {
+                match index {
+                    MENU_RESUME => return Ok(SceneSwitch::Pop),
+                    MENU_RESTART => {
+                        let gameplay = GameplayScene::new(ctx, shared, self.level_entry.clone())?;
+                        return Ok(SceneSwitch::Reset(Box::new(gameplay)));
+                    }
+                    MENU_OPTIONS => {
+                        let options = OptionsScene::new(ctx, shared)?;
+                        return Ok(SceneSwitch::Push(Box::new(options)));
+                    }
+                    MENU_LEVEL_SELECT => {
+                        let level_select = LevelSelectScene::new(ctx, shared)?;
+                        return Ok(SceneSwitch::Reset(Box::new(level_select)));
+                    }
+                    MENU_QUIT_TO_TITLE => {
+                        let title = TitleScene::new(ctx, shared)?;
+                        return Ok(SceneSwitch::Reset(Box::new(title)));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        scenes::draw_overlay(ctx, shared, 0.6)?;
+        scenes::draw_heading(ctx, shared, "Paused", 160.0, 48.0)?;
+        self.gui_manager.render(ctx)
+    }
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if !pressed {
+            return;
+        }
+
+        if button == Buttons::Cancel {
+            self.resume_requested = true;
+            return;
+        }
+
+        let events = self.gui_manager.interact(button);
+        scenes::play_menu_sounds(shared, &events);
+        self.events.extend(events);
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.gui_manager = PauseScene::build_gui(shared);
+        Ok(())
+    }
+
+    fn draw_previous(&self) -> bool {
+        true
+    }
+}
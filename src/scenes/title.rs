@@ -0,0 +1,96 @@
+use std::collections::VecDeque;
+
+use ggez::{Context, GameResult};
+
+use app::SharedState;
+use gui::{GuiManager, MenuEvent, WidgetEvent};
+use input::Buttons;
+use scenes::level_select::LevelSelectScene;
+use scenes::options::OptionsScene;
+use scenes::{self, Scene, SceneSwitch};
+use widgets::menu::Menu;
+
+const MENU_PLAY: i32 = 0;
+const MENU_OPTIONS: i32 = 1;
+const MENU_QUIT: i32 = 2;
+
+pub struct TitleScene {
+    gui_manager: GuiManager,
+    events: VecDeque<WidgetEvent>,
+}
+
+impl TitleScene {
+    pub fn new(_ctx: &mut Context, shared: &mut SharedState) -> GameResult<TitleScene> {
+        scenes::reset_menu_selection(shared);
+
+        Ok(TitleScene {
+            gui_manager: TitleScene::build_gui(shared),
+            events: VecDeque::new(),
+        })
+    }
+
+    fn build_gui(shared: &SharedState) -> GuiManager {
+        let mut gui_manager = GuiManager::new();
+        let position = scenes::menu_position(shared);
+
+        let menu = Menu::new(
+            position.x,
+            position.y,
+            scenes::menu_spacing(shared),
+            shared.assets.font.clone(),
+            shared.assets.default_scale,
+            shared.store.clone(),
+        ).with_option("Play")
+            .with_option("Options")
+            .with_option("Quit");
+
+        gui_manager.widgets.push(menu);
+
+        gui_manager
+    }
+}
+
+impl Scene for TitleScene {
+    fn update(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<SceneSwitch> {
+        while let Some(event) = self.events.pop_front() {
+            if let WidgetEvent::MenuEvent(MenuEvent::Selected(index)) = event {
+                match index {
+                    MENU_PLAY => {
+                        let level_select = LevelSelectScene::new(ctx, shared)?;
+                        return Ok(SceneSwitch::Replace(Box::new(level_select)));
+                    }
+                    MENU_OPTIONS => {
+                        let options = OptionsScene::new(ctx, shared)?;
+                        return Ok(SceneSwitch::Push(Box::new(options)));
+                    }
+                    MENU_QUIT => return Ok(SceneSwitch::Pop),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(SceneSwitch::None)
+    }
+
+    fn draw(&mut self, ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        scenes::draw_heading(ctx, shared, "Puzzle", 160.0, 64.0)?;
+        self.gui_manager.render(ctx)
+    }
+
+    fn input(&mut self, shared: &mut SharedState, button: Buttons, pressed: bool) {
+        if pressed {
+            let events = self.gui_manager.interact(button);
+            scenes::play_menu_sounds(shared, &events);
+            self.events.extend(events);
+        }
+    }
+
+    fn resize(&mut self, _ctx: &mut Context, shared: &mut SharedState) -> GameResult<()> {
+        self.gui_manager = TitleScene::build_gui(shared);
+        Ok(())
+    }
+
+    fn music(&self) -> Option<&'static str> {
+        Some("title")
+    }
+}
@@ -1,6 +1,15 @@
-use ggez::graphics;
+use ggez::graphics::{self, Color, DrawMode, Rect};
 use ggez::{Context, GameResult};
 
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ScaleMode {
+    /// The logical size follows the window, scaled only by the display's DPI.
+    Fit,
+    /// A fixed logical resolution scaled by the largest whole number that
+    /// fits the window, centered with black bars around it.
+    PixelPerfect { width: u32, height: u32 },
+}
+
 #[derive(Debug, Copy, Clone)]
 pub struct Screen {
     pub logical_w: u32,
@@ -9,28 +18,121 @@ pub struct Screen {
     pub screen_h: u32,
     pub scale_w: f32,
     pub scale_h: f32,
+    pub offset_x: f32,
+    pub offset_y: f32,
+    pub scale_mode: ScaleMode,
 }
 
 impl Screen {
     pub fn new(ctx: &mut Context) -> GameResult<Screen> {
-        let (logical_w, logical_h) = graphics::get_size(ctx);
+        Screen::with_scale_mode(ctx, ScaleMode::Fit)
+    }
+
+    /// Measures the window and sets ggez' screen coordinates to drawable
+    /// pixels, so it must be called again whenever the window is resized or
+    /// moved to a display with a different DPI.
+    pub fn with_scale_mode(ctx: &mut Context, scale_mode: ScaleMode) -> GameResult<Screen> {
+        let (window_w, window_h) = graphics::get_size(ctx);
         let (screen_w, screen_h) = graphics::get_drawable_size(ctx);
-        let (scale_w, scale_h) = (
-            (screen_w as f32 / logical_w as f32),
-            (screen_h as f32 / logical_h as f32),
-        );
 
-        Ok(Screen {
-            logical_w,
-            logical_h,
-            screen_w,
-            screen_h,
-            scale_w,
-            scale_h,
-        })
+        let screen = match scale_mode {
+            ScaleMode::Fit => Screen {
+                logical_w: window_w,
+                logical_h: window_h,
+                screen_w,
+                screen_h,
+                scale_w: screen_w as f32 / window_w as f32,
+                scale_h: screen_h as f32 / window_h as f32,
+                offset_x: 0.0,
+                offset_y: 0.0,
+                scale_mode,
+            },
+            ScaleMode::PixelPerfect { width, height } => {
+                let scale = (screen_w / width).min(screen_h / height).max(1);
+
+                Screen {
+                    logical_w: width,
+                    logical_h: height,
+                    screen_w,
+                    screen_h,
+                    scale_w: scale as f32,
+                    scale_h: scale as f32,
+                    offset_x: (screen_w.saturating_sub(width * scale) / 2) as f32,
+                    offset_y: (screen_h.saturating_sub(height * scale) / 2) as f32,
+                    scale_mode,
+                }
+            }
+        };
+
+        graphics::set_screen_coordinates(ctx, Rect::new(0.0, 0.0, screen_w as f32, screen_h as f32))?;
+
+        Ok(screen)
+    }
+
+    /// Whether the drawable size no longer matches, e.g. after the window
+    /// was dragged to a display with a different DPI.
+    pub fn is_stale(&self, ctx: &Context) -> bool {
+        graphics::get_drawable_size(ctx) != (self.screen_w, self.screen_h)
     }
 
     pub fn to_screen_coordinates(&self, coords: graphics::Point2) -> graphics::Point2 {
-        graphics::Point2::new(coords.x * self.scale_w, coords.y * self.scale_h)
+        graphics::Point2::new(
+            coords.x * self.scale_w + self.offset_x,
+            coords.y * self.scale_h + self.offset_y,
+        )
+    }
+
+    pub fn to_logical_coordinates(&self, coords: graphics::Point2) -> graphics::Point2 {
+        graphics::Point2::new(
+            (coords.x - self.offset_x) / self.scale_w,
+            (coords.y - self.offset_y) / self.scale_h,
+        )
+    }
+
+    /// Converts window coordinates, like the mouse position, to the logical
+    /// canvas. The window can be smaller than the drawable area on high DPI
+    /// displays.
+    pub fn window_to_logical(&self, ctx: &Context, x: i32, y: i32) -> graphics::Point2 {
+        let (window_w, window_h) = graphics::get_size(ctx);
+
+        self.to_logical_coordinates(graphics::Point2::new(
+            x as f32 * self.screen_w as f32 / window_w.max(1) as f32,
+            y as f32 * self.screen_h as f32 / window_h.max(1) as f32,
+        ))
+    }
+
+    /// The logical canvas, in screen coordinates.
+    pub fn viewport(&self) -> Rect {
+        Rect::new(
+            self.offset_x,
+            self.offset_y,
+            self.logical_w as f32 * self.scale_w,
+            self.logical_h as f32 * self.scale_h,
+        )
+    }
+
+    /// Covers everything outside the viewport, hiding sprites that were
+    /// drawn past the edges of the logical canvas.
+    pub fn draw_letterbox(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.offset_x <= 0.0 && self.offset_y <= 0.0 {
+            return Ok(());
+        }
+
+        let viewport = self.viewport();
+        let (screen_w, screen_h) = (self.screen_w as f32, self.screen_h as f32);
+        let bars = [
+            Rect::new(0.0, 0.0, screen_w, viewport.top()),
+            Rect::new(0.0, viewport.bottom(), screen_w, screen_h - viewport.bottom()),
+            Rect::new(0.0, viewport.top(), viewport.left(), viewport.h),
+            Rect::new(viewport.right(), viewport.top(), screen_w - viewport.right(), viewport.h),
+        ];
+
+        graphics::set_color(ctx, Color::new(0.0, 0.0, 0.0, 1.0))?;
+
+        for bar in bars.iter().filter(|bar| bar.w > 0.0 && bar.h > 0.0) {
+            graphics::rectangle(ctx, DrawMode::Fill, *bar)?;
+        }
+
+        graphics::set_color(ctx, Color::new(1.0, 1.0, 1.0, 1.0))
     }
 }
@@ -0,0 +1,424 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use ggez::Context;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, FuncArgs, Scope, AST};
+use warmy;
+
+use assets::{self, AssetError};
+use tilemap::{Tile, BACKGROUND_LAYER, ENTITY_LAYER};
+
+/// How much work a script gets each time it runs: its top level, or one
+/// handler. Scripts come with the levels and aren't meant to do much, so
+/// this only stops an endless loop or recursion from freezing the game.
+const MAX_OPERATIONS: u64 = 100_000;
+const MAX_CALL_LEVELS: usize = 32;
+const MAX_STRING_SIZE: usize = 4096;
+
+/// Something that happened in the level, which scripts can listen for with
+/// `listen("plant_grown", "handler")`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptEvent {
+    /// The level has just been set up.
+    Start,
+    PlantGrown { x: i32, y: i32, species: String },
+    PlayerEntered { x: i32, y: i32 },
+}
+
+impl ScriptEvent {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ScriptEvent::Start => "start",
+            ScriptEvent::PlantGrown { .. } => "plant_grown",
+            ScriptEvent::PlayerEntered { .. } => "player_entered",
+        }
+    }
+
+}
+
+/// Events raised by systems this frame, handed to the level's script by the
+/// scene after the dispatcher has run.
+#[derive(Default)]
+pub struct ScriptEvents {
+    pub queue: Vec<ScriptEvent>,
+}
+
+/// Which tile map a script reads or writes, by the name scripts use for it.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MapLayer {
+    /// The `BackgroundMap`, `"background"`.
+    Background,
+    /// The `EntityMap`, `"entity"`.
+    Entity,
+}
+
+impl MapLayer {
+    fn from_name(name: &str) -> Option<MapLayer> {
+        match name {
+            "background" => Some(MapLayer::Background),
+            "entity" => Some(MapLayer::Entity),
+            _ => None,
+        }
+    }
+
+    fn sprite_layer(&self) -> i32 {
+        match *self {
+            MapLayer::Background => BACKGROUND_LAYER,
+            MapLayer::Entity => ENTITY_LAYER,
+        }
+    }
+}
+
+/// What a script asked for. Scripts only ever see a copy of the level, so
+/// the scene carries these out once the script has returned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptCommand {
+    /// Creates a `"block"`, a `"slime"` or a plant of the named species.
+    Spawn { kind: String, x: i32, y: i32 },
+    /// Puts `tile` on the map, or takes the tile there away if `None`.
+    SetTile {
+        layer: MapLayer,
+        position: (i32, i32),
+        tile: Option<Tile>,
+    },
+    /// Shows `text` in a dialog once any open one is closed.
+    Say { text: String },
+}
+
+/// The source of a level's script, e.g. `/scripts/first-sprouts.rhai`.
+#[derive(Debug, Clone)]
+pub struct Script {
+    pub source: String,
+    pub generation: usize,
+}
+
+impl warmy::Load<Context> for Script {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let bytes = assets::read_bytes(&key, store.root(), ctx)?;
+        let source = String::from_utf8(bytes).map_err(|e| AssetError::decode(key.as_path(), e))?;
+
+        Ok(warmy::Loaded::from(Script {
+            source,
+            generation: assets::next_generation(),
+        }))
+    }
+}
+
+/// What the bindings share with the host: the tile maps as of the last
+/// sync, the handlers listening for each event and the commands so far.
+#[derive(Default)]
+struct ScriptState {
+    background: HashMap<(i32, i32), Tile>,
+    entity_tiles: HashMap<(i32, i32), Tile>,
+    listeners: HashMap<String, Vec<String>>,
+    commands: Vec<ScriptCommand>,
+}
+
+impl ScriptState {
+    fn map(&mut self, layer: MapLayer) -> &mut HashMap<(i32, i32), Tile> {
+        match layer {
+            MapLayer::Background => &mut self.background,
+            MapLayer::Entity => &mut self.entity_tiles,
+        }
+    }
+
+    /// Changes the copy of the map straight away, so the script reads back
+    /// what it wrote, and queues the change for the real one.
+    fn set_tile(&mut self, layer_name: &str, position: (i32, i32), tile: Option<Tile>) -> Result<(), String> {
+        let layer = match MapLayer::from_name(layer_name) {
+            Some(layer) => layer,
+            None => return Err(format!("there is no {:?} map", layer_name)),
+        };
+
+        match tile {
+            Some(ref tile) => {
+                self.map(layer).insert(position, tile.clone());
+            }
+            None => {
+                self.map(layer).remove(&position);
+            }
+        }
+
+        self.commands.push(ScriptCommand::SetTile { layer, position, tile });
+
+        Ok(())
+    }
+
+    fn tile(&mut self, layer_name: &str, position: (i32, i32)) -> Option<&Tile> {
+        match MapLayer::from_name(layer_name) {
+            Some(layer) => self.map(layer).get(&position),
+            None => None,
+        }
+    }
+}
+
+fn register_bindings(engine: &mut Engine, state: &Rc<RefCell<ScriptState>>) {
+    let shared = state.clone();
+    engine.register_fn("listen", move |event: String, handler: String| {
+        shared.borrow_mut().listeners.entry(event).or_insert_with(Vec::new).push(handler);
+    });
+
+    let shared = state.clone();
+    engine.register_fn("spawn", move |kind: String, x: i64, y: i64| {
+        shared.borrow_mut().commands.push(ScriptCommand::Spawn {
+            kind,
+            x: x as i32,
+            y: y as i32,
+        });
+    });
+
+    let shared = state.clone();
+    engine.register_fn("say", move |text: String| {
+        shared.borrow_mut().commands.push(ScriptCommand::Say { text });
+    });
+
+    let shared = state.clone();
+    engine.register_fn("tile", move |layer: String, x: i64, y: i64| -> String {
+        match shared.borrow_mut().tile(&layer, (x as i32, y as i32)) {
+            Some(tile) => tile.tileset.clone(),
+            None => String::new(),
+        }
+    });
+
+    let shared = state.clone();
+    engine.register_fn("sprite", move |layer: String, x: i64, y: i64| -> i64 {
+        match shared.borrow_mut().tile(&layer, (x as i32, y as i32)) {
+            Some(tile) => tile.sprite_id as i64,
+            None => -1,
+        }
+    });
+
+    let shared = state.clone();
+    engine.register_fn(
+        "set_tile",
+        move |layer: String, x: i64, y: i64, tileset: String, sprite_id: i64| -> Result<(), Box<EvalAltResult>> {
+            let sprite_layer = match MapLayer::from_name(&layer) {
+                Some(map) => map.sprite_layer(),
+                None => BACKGROUND_LAYER,
+            };
+            let tile = Tile::new(sprite_layer, &tileset, sprite_id as i32);
+
+            Ok(shared.borrow_mut().set_tile(&layer, (x as i32, y as i32), Some(tile))?)
+        },
+    );
+
+    let shared = state.clone();
+    engine.register_fn(
+        "clear_tile",
+        move |layer: String, x: i64, y: i64| -> Result<(), Box<EvalAltResult>> {
+            Ok(shared.borrow_mut().set_tile(&layer, (x as i32, y as i32), None)?)
+        },
+    );
+}
+
+/// Runs a level's script. Scripts are written in Rhai with `import` turned
+/// off, so they can't reach the filesystem or anything else beyond the
+/// functions registered here, and are stopped with an error once they go
+/// over their budget of operations:
+///
+/// - `listen(event, handler)` calls the function named `handler` whenever
+///   `event` happens, see `ScriptEvent` for the events and their arguments:
+///   `plant_grown` handlers take `x`, `y` and `species`, `player_entered`
+///   ones `x` and `y`, and `start` ones nothing.
+/// - `spawn(kind, x, y)` creates a block, a slime or a plant.
+/// - `tile(map, x, y)` and `sprite(map, x, y)` read the tileset and sprite
+///   of a tile of the `"background"` or `"entity"` map, or `""` and `-1`
+///   where there's none; `set_tile(map, x, y, tileset, sprite)` and
+///   `clear_tile(map, x, y)` change it.
+/// - `say(text)` shows a dialog.
+pub struct ScriptHost {
+    engine: Engine,
+    ast: AST,
+    state: Rc<RefCell<ScriptState>>,
+}
+
+impl ScriptHost {
+    /// Runs the script's top level, which defines its handlers and starts
+    /// listening for events.
+    pub fn new(source: &str) -> Result<ScriptHost, String> {
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let mut engine = Engine::new();
+
+        engine
+            .set_module_resolver(DummyModuleResolver::new())
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(MAX_CALL_LEVELS)
+            .set_max_string_size(MAX_STRING_SIZE);
+        register_bindings(&mut engine, &state);
+
+        let ast = engine.compile(source).map_err(|e| e.to_string())?;
+        engine.run_ast(&ast).map_err(|e| e.to_string())?;
+
+        Ok(ScriptHost { engine, ast, state })
+    }
+
+    /// Calls the script's function `handler`. Whatever it returns is ignored.
+    fn call<A: FuncArgs>(&self, handler: &str, arguments: A) -> Result<(), String> {
+        // The top level already ran when the script started.
+        let options = CallFnOptions::new().eval_ast(false);
+
+        self.engine
+            .call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, handler, arguments)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Gives the script a fresh copy of the tile maps to read.
+    pub fn sync_tiles(&mut self, background: &HashMap<(i32, i32), Tile>, entity_tiles: &HashMap<(i32, i32), Tile>) {
+        let mut state = self.state.borrow_mut();
+
+        state.background = background.clone();
+        state.entity_tiles = entity_tiles.clone();
+    }
+
+    /// Calls every handler listening for `event`. A failing handler doesn't
+    /// stop the ones after it; the first error is returned.
+    pub fn handle(&mut self, event: &ScriptEvent) -> Result<(), String> {
+        let handlers = self.state
+            .borrow()
+            .listeners
+            .get(event.name())
+            .cloned()
+            .unwrap_or_default();
+        let mut result = Ok(());
+
+        for handler in handlers {
+            let called = match *event {
+                ScriptEvent::Start => self.call(&handler, ()),
+                ScriptEvent::PlantGrown { x, y, ref species } => {
+                    self.call(&handler, (i64::from(x), i64::from(y), species.clone()))
+                }
+                ScriptEvent::PlayerEntered { x, y } => self.call(&handler, (i64::from(x), i64::from(y))),
+            };
+
+            if let Err(e) = called {
+                if result.is_ok() {
+                    result = Err(format!("{}: {}", handler, e));
+                }
+            }
+        }
+
+        result
+    }
+
+    /// What the script has asked for since the last call.
+    pub fn take_commands(&mut self) -> Vec<ScriptCommand> {
+        self.state.borrow_mut().commands.drain(..).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    fn host(source: &str) -> ScriptHost {
+        ScriptHost::new(source).expect("script starts")
+    }
+
+    #[test]
+    fn handlers_are_called_with_the_events_details() {
+        let mut host = host(
+            r#"
+            fn grown(x, y, species) { say(species + " at " + x + ", " + y); }
+            listen("plant_grown", "grown");
+            "#,
+        );
+
+        host.handle(&ScriptEvent::PlantGrown {
+            x: 2,
+            y: 3,
+            species: "daisy".to_string(),
+        }).expect("handler runs");
+        host.handle(&ScriptEvent::PlayerEntered { x: 2, y: 3 })
+            .expect("nothing listens");
+
+        assert_eq!(
+            host.take_commands(),
+            vec![ScriptCommand::Say {
+                text: "daisy at 2, 3".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn scripts_read_back_the_tiles_they_set() {
+        let mut host = host(
+            r#"
+            fn entered(x, y) {
+                set_tile("background", x, y, "grass", 2);
+                say(tile("background", x, y) + " " + sprite("background", x, y));
+            }
+            listen("player_entered", "entered");
+            "#,
+        );
+
+        host.handle(&ScriptEvent::PlayerEntered { x: 1, y: 0 })
+            .expect("handler runs");
+
+        assert_eq!(
+            host.take_commands(),
+            vec![
+                ScriptCommand::SetTile {
+                    layer: MapLayer::Background,
+                    position: (1, 0),
+                    tile: Some(Tile::new(BACKGROUND_LAYER, "grass", 2)),
+                },
+                ScriptCommand::Say {
+                    text: "grass 2".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn mistakes_are_errors() {
+        assert!(ScriptHost::new("set_tile(").is_err());
+        assert!(ScriptHost::new(r#"set_tile("sky", 0, 0, "grass", 0);"#).is_err());
+
+        let mut host = host(r#"listen("start", "missing");"#);
+
+        assert!(host.handle(&ScriptEvent::Start).is_err());
+    }
+
+    #[test]
+    fn scripts_cant_import_files() {
+        let module = env::temp_dir().join("puzzle-script-import-test");
+        fs::write(module.with_extension("rhai"), "fn peek() { 1 }").expect("module is written");
+
+        let import = format!("import {:?} as m; m::peek();", module.to_string_lossy());
+
+        assert!(ScriptHost::new(r#"import "x" as m;"#).is_err());
+        assert!(ScriptHost::new(&import).is_err());
+    }
+
+    #[test]
+    fn endless_scripts_are_stopped() {
+        assert!(ScriptHost::new("loop {}").is_err());
+
+        let mut host = host(
+            r#"
+            fn spin() { loop {} }
+            fn deeper(n) { deeper(n + 1) }
+            fn start() { deeper(0) }
+            listen("start", "spin");
+            listen("start", "start");
+            "#,
+        );
+
+        let error = host.handle(&ScriptEvent::Start).expect_err("handlers are stopped");
+
+        assert!(error.starts_with("spin: "));
+    }
+}
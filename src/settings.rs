@@ -0,0 +1,166 @@
+use std::io::{Read, Write};
+use std::path;
+
+use ggez::conf::{FullscreenType, WindowMode, WindowSetup};
+use ggez::filesystem::Filesystem;
+use ggez::{GameError, GameResult};
+use toml;
+
+use screen::ScaleMode;
+
+pub const SETTINGS_PATH: &str = "/settings.toml";
+
+pub const RESOLUTIONS: [(u32, u32); 4] = [(1024, 720), (1280, 720), (1600, 900), (1920, 1080)];
+pub const UI_SCALES: [f32; 5] = [0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// Logical resolution used when pixel-perfect scaling is on. It's scaled by
+/// whole numbers only, so 1920x1080 doubles it and smaller windows get bars.
+pub const PIXEL_PERFECT_WIDTH: u32 = 960;
+pub const PIXEL_PERFECT_HEIGHT: u32 = 540;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DisplayMode {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+impl DisplayMode {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            DisplayMode::Windowed => "Windowed",
+            DisplayMode::Fullscreen => "Fullscreen",
+            DisplayMode::Borderless => "Borderless",
+        }
+    }
+
+    pub fn cycle(&self, step: i32) -> DisplayMode {
+        let modes = [DisplayMode::Windowed, DisplayMode::Fullscreen, DisplayMode::Borderless];
+        let index = modes.iter().position(|mode| mode == self).unwrap_or(0);
+
+        modes[cycle_index(index, modes.len(), step)]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub width: u32,
+    pub height: u32,
+    pub display_mode: DisplayMode,
+    pub vsync: bool,
+    pub pixel_perfect: bool,
+    pub ui_scale: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            width: 1024,
+            height: 720,
+            display_mode: DisplayMode::Windowed,
+            vsync: true,
+            pixel_perfect: false,
+            ui_scale: 1.0,
+            music_volume: 0.8,
+            sfx_volume: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    /// Reads the settings from the user config directory, or gives the
+    /// defaults if there aren't any saved yet. A file that can't be read or
+    /// parsed is an error rather than silently the defaults.
+    pub fn load(filesystem: &mut Filesystem) -> GameResult<Settings> {
+        let settings_path = path::Path::new(SETTINGS_PATH);
+
+        if !filesystem.is_file(settings_path) {
+            return Ok(Settings::default());
+        }
+
+        let mut contents = String::new();
+        filesystem.open(settings_path)?.read_to_string(&mut contents)?;
+
+        toml::from_str(&contents)
+            .map_err(|e| GameError::ConfigError(format!("Could not parse {}: {}", SETTINGS_PATH, e)))
+    }
+
+    pub fn save(&self, filesystem: &mut Filesystem) -> GameResult<()> {
+        let contents = toml::to_string(self)
+            .map_err(|e| GameError::ConfigError(format!("Could not serialize settings: {}", e)))?;
+        let mut file = filesystem.create(SETTINGS_PATH)?;
+
+        file.write_all(contents.as_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn window_setup(&self) -> WindowSetup {
+        WindowSetup::default()
+            .title("Puzzle")
+            .resizable(true)
+            .allow_highdpi(true)
+            .samples(1)
+            .expect("Invalid window config")
+    }
+
+    pub fn window_mode(&self) -> WindowMode {
+        let (fullscreen_type, borderless) = match self.display_mode {
+            DisplayMode::Windowed => (FullscreenType::Off, false),
+            DisplayMode::Fullscreen => (FullscreenType::True, false),
+            DisplayMode::Borderless => (FullscreenType::Desktop, true),
+        };
+
+        WindowMode::default()
+            .dimensions(self.width, self.height)
+            .fullscreen_type(fullscreen_type)
+            .borderless(borderless)
+            .vsync(self.vsync)
+    }
+
+    pub fn scale_mode(&self) -> ScaleMode {
+        if self.pixel_perfect {
+            ScaleMode::PixelPerfect {
+                width: PIXEL_PERFECT_WIDTH,
+                height: PIXEL_PERFECT_HEIGHT,
+            }
+        } else {
+            ScaleMode::Fit
+        }
+    }
+
+    pub fn cycle_resolution(&mut self, step: i32) {
+        let index = RESOLUTIONS
+            .iter()
+            .position(|&resolution| resolution == (self.width, self.height))
+            .unwrap_or(0);
+        let (width, height) = RESOLUTIONS[cycle_index(index, RESOLUTIONS.len(), step)];
+
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn cycle_ui_scale(&mut self, step: i32) {
+        let index = UI_SCALES
+            .iter()
+            .position(|&ui_scale| ui_scale == self.ui_scale)
+            .unwrap_or(1);
+
+        self.ui_scale = UI_SCALES[cycle_index(index, UI_SCALES.len(), step)];
+    }
+}
+
+/// Moves a volume up or down a tenth, staying within 0 and 1.
+pub fn step_volume(volume: f32, step: i32) -> f32 {
+    ((volume * 10.0).round() + step as f32).max(0.0).min(10.0) / 10.0
+}
+
+fn cycle_index(index: usize, len: usize, step: i32) -> usize {
+    let len = len as i32;
+
+    ((index as i32 + step % len + len) % len) as usize
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use ggez::{Context, GameResult};
+use warmy;
+use warmy::load::Store;
+
+use assets::{self, AssetError};
+use tilemap::{Tile, ENTITY_LAYER};
+
+pub const SPECIES_INDEX_PATH: &str = "/plants.toml";
+
+/// One look a plant has while it grows.
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrowthStage {
+    pub tileset: String,
+    #[serde(default)]
+    pub sprite_id: i32,
+    /// Turns spent in this stage on the most fertile soil; poorer soil takes
+    /// proportionally longer. The last stage lasts forever.
+    #[serde(default = "default_stage_turns")]
+    pub turns: f32,
+}
+
+fn default_stage_turns() -> f32 {
+    1.0
+}
+
+/// How a grown plant reproduces, every `every` turns.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Spread {
+    /// Drops seeds of its own species on empty neighbouring tiles.
+    Seed { every: u32 },
+    /// Creeps onto empty neighbouring tiles that are next to something
+    /// `Solid`, the way vines follow a wall.
+    Climb { every: u32 },
+    /// Takes over neighbouring plants of other species.
+    Overrun { every: u32 },
+}
+
+/// Everything that makes one kind of plant different from another, as
+/// defined in `plants.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Species {
+    pub name: String,
+    pub stages: Vec<GrowthStage>,
+    /// Moisture the plant needs on its tile to grow, and how much each stage
+    /// uses up.
+    pub water_need: f32,
+    pub water_use: f32,
+    /// Sunlight the plant needs to grow; the tile below something tall gets
+    /// less.
+    pub light_need: f32,
+    #[serde(default)]
+    pub spread: Vec<Spread>,
+    /// Grown plants of this species block the way like a wall.
+    #[serde(default)]
+    pub solid_when_grown: bool,
+    /// Grown plants of this species count towards `grow_flowers` objectives;
+    /// weeds, vines and the like don't.
+    #[serde(default)]
+    pub flower: bool,
+}
+
+impl Species {
+    pub fn last_stage(&self) -> u32 {
+        (self.stages.len() as u32).max(1) - 1
+    }
+
+    pub fn is_grown(&self, stage: u32) -> bool {
+        stage >= self.last_stage()
+    }
+
+    /// Growth needed to get out of `stage`, or `None` if it's the last one.
+    pub fn stage_turns(&self, stage: u32) -> Option<f32> {
+        if self.is_grown(stage) {
+            return None;
+        }
+
+        self.stages.get(stage as usize).map(|stage| stage.turns)
+    }
+
+    pub fn tile(&self, stage: u32) -> Tile {
+        match self.stages.get(stage.min(self.last_stage()) as usize) {
+            Some(stage) => Tile::new(ENTITY_LAYER, &stage.tileset, stage.sprite_id),
+            None => Tile::new(ENTITY_LAYER, "seed", 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpeciesIndex {
+    pub species: Vec<Species>,
+    #[serde(skip)]
+    pub generation: usize,
+}
+
+impl warmy::Load<Context> for SpeciesIndex {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let mut index: SpeciesIndex = assets::read_toml(&key, store.root(), ctx)?;
+        index.generation = assets::next_generation();
+
+        Ok(warmy::Loaded::from(index))
+    }
+}
+
+/// The species definitions as the gameplay systems see them. A plain copy
+/// of the index, since warmy's handles can't be shared with the world.
+#[derive(Debug, Clone, Default)]
+pub struct SpeciesTable {
+    species: HashMap<String, Species>,
+    /// Generation of the index this was copied from.
+    pub generation: usize,
+}
+
+impl SpeciesTable {
+    pub fn new(species: Vec<Species>, generation: usize) -> SpeciesTable {
+        SpeciesTable {
+            species: species
+                .into_iter()
+                .map(|species| (species.name.clone(), species))
+                .collect(),
+            generation,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Species> {
+        self.species.get(name)
+    }
+}
+
+/// Keeps hold of the species index so warmy reloads it when the file
+/// changes, letting a level pick up new growth numbers while it's played.
+pub struct SpeciesRegistry {
+    index: Option<warmy::Res<SpeciesIndex>>,
+}
+
+impl SpeciesRegistry {
+    /// A registry without any species, until the real one is loaded.
+    pub fn empty() -> SpeciesRegistry {
+        SpeciesRegistry { index: None }
+    }
+
+    pub fn load(asset_store: &mut Store<Context>, ctx: &mut Context) -> GameResult<SpeciesRegistry> {
+        let index = assets::get::<SpeciesIndex>(asset_store, SPECIES_INDEX_PATH, ctx)?;
+
+        Ok(SpeciesRegistry { index: Some(index) })
+    }
+
+    /// Changes whenever warmy reloads the index.
+    pub fn generation(&self) -> usize {
+        self.index.as_ref().map_or(0, |index| index.borrow().generation)
+    }
+
+    pub fn table(&self) -> SpeciesTable {
+        let index = match self.index {
+            Some(ref index) => index.borrow(),
+            None => return SpeciesTable::default(),
+        };
+
+        SpeciesTable::new(index.species.clone(), index.generation)
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use species::{Spread, SpeciesTable};
+
+/// What is on a tile, as far as spreading plants are concerned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    /// Ground a seed could take root in.
+    Open,
+    /// Water or bare path; nothing grows there.
+    Barren,
+    /// Something `Solid` that isn't a plant, like a block.
+    Solid,
+    /// Something in the way that isn't solid, like the player.
+    Occupied,
+    Plant { species: String, grown: bool, solid: bool },
+}
+
+/// A snapshot of the level taken before plants spread. Rules only ever read
+/// from it, so every plant sees the same grid no matter which is evaluated
+/// first. Tiles outside of the level aren't in `cells`.
+#[derive(Debug, Clone, Default)]
+pub struct Grid {
+    pub cells: HashMap<(i32, i32), Cell>,
+}
+
+impl Grid {
+    pub fn get(&self, position: (i32, i32)) -> Option<&Cell> {
+        self.cells.get(&position)
+    }
+
+    fn is_open(&self, position: (i32, i32)) -> bool {
+        self.get(position) == Some(&Cell::Open)
+    }
+
+    fn is_solid(&self, position: (i32, i32)) -> bool {
+        match self.get(position) {
+            Some(&Cell::Solid) | Some(&Cell::Plant { solid: true, .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+fn neighbours((x, y): (i32, i32)) -> [(i32, i32); 4] {
+    [(x, y - 1), (x + 1, y), (x, y + 1), (x - 1, y)]
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ChangeKind {
+    /// A new seed on an open tile.
+    Sprout,
+    /// The plant on the tile is replaced by a seed of another species.
+    Overrun,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub position: (i32, i32),
+    pub species: String,
+    pub kind: ChangeKind,
+}
+
+/// Tiles `rule` would claim for a grown plant at `position` this turn.
+pub fn claims(rule: &Spread, species: &str, position: (i32, i32), grid: &Grid, turn: u32) -> Vec<(i32, i32)> {
+    let every = match *rule {
+        Spread::Seed { every } | Spread::Climb { every } | Spread::Overrun { every } => every,
+    };
+
+    if every == 0 || turn % every != 0 {
+        return Vec::new();
+    }
+
+    neighbours(position)
+        .iter()
+        .cloned()
+        .filter(|&target| match *rule {
+            Spread::Seed { .. } => grid.is_open(target),
+            Spread::Climb { .. } => {
+                grid.is_open(target) && neighbours(target).iter().any(|&wall| grid.is_solid(wall))
+            }
+            Spread::Overrun { .. } => match grid.get(target) {
+                Some(&Cell::Plant { species: ref other, grown, .. }) => other != species && !grown,
+                _ => false,
+            },
+        })
+        .collect()
+}
+
+/// Evaluates the spreading rules of every grown plant on `grid`. When
+/// different species claim the same tile they hold each other off and the
+/// tile stays as it is. Changes are sorted by position, top to bottom.
+pub fn spread(grid: &Grid, species: &SpeciesTable, turn: u32) -> Vec<Change> {
+    let mut claimed: HashMap<(i32, i32), Option<String>> = HashMap::new();
+
+    for (&position, cell) in grid.cells.iter() {
+        let name = match *cell {
+            Cell::Plant { species: ref name, grown: true, .. } => name,
+            _ => continue,
+        };
+
+        let rules = match species.get(name) {
+            Some(kind) => &kind.spread,
+            None => continue,
+        };
+
+        for rule in rules.iter() {
+            for target in claims(rule, name, position, grid, turn) {
+                let claim = claimed.entry(target).or_insert_with(|| Some(name.clone()));
+
+                if claim.as_ref() != Some(name) {
+                    *claim = None;
+                }
+            }
+        }
+    }
+
+    let mut changes: Vec<Change> = claimed
+        .into_iter()
+        .filter_map(|(position, claim)| {
+            claim.map(|species| Change {
+                position,
+                species,
+                kind: match grid.get(position) {
+                    Some(&Cell::Plant { .. }) => ChangeKind::Overrun,
+                    _ => ChangeKind::Sprout,
+                },
+            })
+        })
+        .collect();
+
+    changes.sort_by_key(|change| (change.position.1, change.position.0));
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use species::Species;
+
+    fn species(name: &str, spread: Vec<Spread>) -> Species {
+        Species {
+            name: name.to_string(),
+            stages: Vec::new(),
+            water_need: 0.0,
+            water_use: 0.0,
+            light_need: 0.0,
+            spread,
+            solid_when_grown: false,
+            flower: false,
+        }
+    }
+
+    /// Flowers seeding every turn, ivy climbing every turn and thistles
+    /// overrunning every turn.
+    fn table() -> SpeciesTable {
+        SpeciesTable::new(
+            vec![
+                species("daisy", vec![Spread::Seed { every: 1 }]),
+                species("clover", vec![Spread::Seed { every: 3 }]),
+                species("ivy", vec![Spread::Climb { every: 1 }]),
+                species("thistle", vec![Spread::Overrun { every: 1 }]),
+            ],
+            0,
+        )
+    }
+
+    fn plant(species: &str, grown: bool) -> Cell {
+        Cell::Plant {
+            species: species.to_string(),
+            grown,
+            solid: false,
+        }
+    }
+
+    fn grid(cells: Vec<((i32, i32), Cell)>) -> Grid {
+        Grid {
+            cells: cells.into_iter().collect(),
+        }
+    }
+
+    fn sprout(position: (i32, i32), species: &str) -> Change {
+        Change {
+            position,
+            species: species.to_string(),
+            kind: ChangeKind::Sprout,
+        }
+    }
+
+    #[test]
+    fn flowers_seed_open_cells() {
+        let grid = grid(vec![
+            ((1, 1), plant("daisy", true)),
+            ((1, 0), Cell::Open),
+            ((2, 1), Cell::Barren),
+            ((1, 2), Cell::Occupied),
+            ((0, 1), Cell::Open),
+        ]);
+
+        assert_eq!(
+            spread(&grid, &table(), 1),
+            vec![sprout((1, 0), "daisy"), sprout((0, 1), "daisy")]
+        );
+    }
+
+    #[test]
+    fn only_grown_plants_spread_and_only_on_their_turns() {
+        let seedling = grid(vec![((0, 0), plant("daisy", false)), ((1, 0), Cell::Open)]);
+        let clover = grid(vec![((0, 0), plant("clover", true)), ((1, 0), Cell::Open)]);
+
+        assert!(spread(&seedling, &table(), 1).is_empty());
+        assert!(spread(&clover, &table(), 2).is_empty());
+        assert_eq!(spread(&clover, &table(), 3), vec![sprout((1, 0), "clover")]);
+    }
+
+    #[test]
+    fn vines_climb_next_to_solid() {
+        let mut grid = grid(vec![
+            ((0, 1), plant("ivy", true)),
+            ((0, 0), Cell::Open),
+            ((1, 1), Cell::Open),
+            ((2, 1), Cell::Solid),
+            ((0, 2), Cell::Open),
+            ((-1, 2), plant("bramble", true)),
+        ]);
+
+        // The bramble isn't solid yet, so only the tile by the block counts.
+        assert_eq!(spread(&grid, &table(), 1), vec![sprout((1, 1), "ivy")]);
+
+        grid.cells.insert(
+            (-1, 2),
+            Cell::Plant {
+                species: "bramble".to_string(),
+                grown: true,
+                solid: true,
+            },
+        );
+
+        assert_eq!(
+            spread(&grid, &table(), 1),
+            vec![sprout((1, 1), "ivy"), sprout((0, 2), "ivy")]
+        );
+    }
+
+    #[test]
+    fn weeds_overrun_other_seedlings() {
+        let grid = grid(vec![
+            ((1, 0), plant("thistle", true)),
+            ((0, 0), plant("daisy", false)),
+            ((2, 0), plant("daisy", true)),
+            ((1, 1), plant("thistle", false)),
+            ((1, -1), Cell::Open),
+        ]);
+
+        assert_eq!(
+            spread(&grid, &table(), 1),
+            vec![Change {
+                position: (0, 0),
+                species: "thistle".to_string(),
+                kind: ChangeKind::Overrun,
+            }]
+        );
+    }
+
+    #[test]
+    fn species_claiming_the_same_cell_hold_each_other_off() {
+        let contested = grid(vec![
+            ((0, 0), plant("daisy", true)),
+            ((1, 0), Cell::Open),
+            ((2, 0), plant("ivy", true)),
+            ((1, 1), Cell::Solid),
+        ]);
+        let shared = grid(vec![
+            ((0, 0), plant("daisy", true)),
+            ((1, 0), Cell::Open),
+            ((2, 0), plant("daisy", true)),
+        ]);
+
+        assert!(spread(&contested, &table(), 1).is_empty());
+        assert_eq!(spread(&shared, &table(), 1), vec![sprout((1, 0), "daisy")]);
+    }
+
+    #[test]
+    fn results_dont_depend_on_the_order_plants_are_visited_in() {
+        let mut cells = vec![
+            ((0, 0), plant("daisy", true)),
+            ((1, 0), Cell::Open),
+            ((2, 0), Cell::Open),
+            ((3, 0), plant("thistle", true)),
+            ((4, 0), plant("daisy", false)),
+            ((0, 1), Cell::Open),
+            ((3, 1), Cell::Open),
+        ];
+        let expected = vec![
+            sprout((1, 0), "daisy"),
+            Change {
+                position: (4, 0),
+                species: "thistle".to_string(),
+                kind: ChangeKind::Overrun,
+            },
+            sprout((0, 1), "daisy"),
+        ];
+
+        // Fresh maps are iterated in a different order each time, and the
+        // sprouts from this turn mustn't spread again before the next one.
+        for _ in 0..8 {
+            assert_eq!(spread(&grid(cells.clone()), &table(), 1), expected);
+            cells.reverse();
+        }
+    }
+}
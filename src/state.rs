@@ -1,3 +1,4 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::rc::Rc;
 
@@ -10,7 +11,7 @@ pub enum MenuAction {
     Up,
     Down,
     // Select,
-    // SetActiveMenuItemIndex(i32),
+    SetActiveMenuItemIndex(i32),
 }
 
 pub struct Store {
@@ -20,12 +21,12 @@ pub struct Store {
 }
 
 impl Store {
-    pub fn new() -> Rc<Store> {
-        Rc::new(Store {
+    pub fn new() -> Rc<RefCell<Store>> {
+        Rc::new(RefCell::new(Store {
             queue: VecDeque::new(),
             selected_menu_item_index: 0,
             menu_stack: vec![0],
-        })
+        }))
     }
 
     pub fn dispatch(&mut self, action: Action) {
@@ -39,9 +40,9 @@ impl Store {
                     // MenuAction::PushActiveMenuId(menu_id) => {
                     //     self.menu_stack.push(menu_id);
                     // }
-                    // MenuAction::SetActiveMenuItemIndex(item_index) => {
-                    //     self.selected_menu_item_index = item_index;
-                    // }
+                    MenuAction::SetActiveMenuItemIndex(item_index) => {
+                        self.selected_menu_item_index = item_index;
+                    }
                     MenuAction::Up => {
                         self.selected_menu_item_index -= 1;
                     }
@@ -1,24 +1,291 @@
-use std::time::{Instant, Duration};
-
 use specs::prelude::*;
+use specs::storage::MaskedStorage;
+
+use std::collections::{HashMap, HashSet};
+use std::iter;
+use std::ops::Deref;
+use std::time::Duration;
 
+use ai;
+use audio::SoundEffect;
+use collision::{self, Occupancy, Occupant};
 use components::{self, MoveAction, AnimationEvent};
+use environment::{Environment, SATURATED};
+use goals::{self, GoalStatus, Goals, LevelEvent, LevelEvents};
+use history::{History, PlantRecord, TurnRecord};
+use pathfinding::{self, PathGrid, Pathfinder};
 use resources;
+use scripting::{ScriptEvent, ScriptEvents};
 use input::{ControllerState, Buttons};
+use level::Terrain;
+use mechanisms::{self, ReactorKind, TriggerKind};
+use species::{Species, SpeciesTable};
+use spread::{self, Cell, ChangeKind, Grid};
+use tween::Tween;
 
+/// Grows plants once per turn, as far as their species' needs and their
+/// tile's moisture, sunlight and fertility allow, then lets grown plants
+/// spread by their species' rules. When the species definitions are
+/// reloaded, every plant's sprite and solidity is brought up to date
+/// straight away.
 #[derive(Default)]
 pub struct Plantae {
-    pub ticks: u32,
+    pub turn: u32,
+    pub species_generation: usize,
 }
 
 impl<'a> System<'a> for Plantae {
     type SystemData = (
-        Write<'a, resources::EntityMap>,
+        Read<'a, resources::TurnCounter>,
+        Read<'a, resources::GameClock>,
+        Read<'a, SpeciesTable>,
+        Write<'a, Environment>,
+        Write<'a, resources::SoundEffects>,
+        Entities<'a>,
         WriteStorage<'a, components::Plantae>,
+        WriteStorage<'a, components::Sprite>,
+        WriteStorage<'a, components::Animation>,
+        WriteStorage<'a, components::Solid>,
+        WriteStorage<'a, components::Position>,
+        ReadStorage<'a, components::Fixture>,
+        Write<'a, ScriptEvents>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            turn_counter,
+            clock,
+            species,
+            mut environment,
+            mut sound_effects,
+            entities,
+            mut plantae,
+            mut sprites,
+            mut animations,
+            mut solids,
+            mut positions,
+            fixtures,
+            mut script_events,
+        ): Self::SystemData,
+    ) {
+        // Undoing takes the counter back, which doesn't grow anything.
+        let new_turn = turn_counter.turns > self.turn;
+        let reloaded = species.generation != self.species_generation;
+
+        self.turn = turn_counter.turns;
+        self.species_generation = species.generation;
+
+        if !new_turn && !reloaded {
+            return;
+        }
+
+        for (entity, plant, sprite, animation, position) in
+            (&*entities, &mut plantae, &mut sprites, &mut animations, &positions).join()
+        {
+            let kind = match species.get(&plant.species) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            if reloaded {
+                plant.stage = plant.stage.min(kind.last_stage());
+            }
+
+            if new_turn && grow(plant, kind, &mut environment, (position.x, position.y)) {
+                animation.animation_queue.push_back(AnimationEvent::Tween(Tween::pop(clock.now())));
+                sound_effects.queue.push(SoundEffect::Grow);
+            }
+
+            let was_grown = plant.grown;
+            plant.grown = kind.is_grown(plant.stage);
+            sprite.tile = kind.tile(plant.stage);
+
+            if new_turn && plant.grown && !was_grown {
+                script_events.queue.push(ScriptEvent::PlantGrown {
+                    x: position.x,
+                    y: position.y,
+                    species: plant.species.clone(),
+                });
+            }
+
+            if plant.grown && kind.solid_when_grown {
+                solids.insert(entity, components::Solid).expect("plant entity is alive");
+            } else {
+                solids.remove(entity);
+            }
+        }
+
+        if !new_turn {
+            return;
+        }
+
+        let (grid, plants_at) = spread_grid(&environment, &entities, &positions, &plantae, &solids, &fixtures);
+        let changes = spread::spread(&grid, &species, self.turn);
+        let now = clock.now();
+
+        for change in changes.iter() {
+            let kind = match species.get(&change.species) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            // An overrun plant starts over as a seed of the new species.
+            let entity = match change.kind {
+                ChangeKind::Sprout => entities.create(),
+                ChangeKind::Overrun => match plants_at.get(&change.position) {
+                    Some(&entity) => entity,
+                    None => continue,
+                },
+            };
+
+            let mut animation = components::Animation::new();
+            animation.animation_queue.push_back(AnimationEvent::Tween(Tween::pop(now)));
+
+            let (x, y) = change.position;
+
+            positions.insert(entity, components::Position::new(x, y)).expect("plant entity is alive");
+            plantae.insert(entity, components::Plantae::new(&kind.name)).expect("plant entity is alive");
+            sprites
+                .insert(entity, components::Sprite { tile: kind.tile(0), z: 0 })
+                .expect("plant entity is alive");
+            animations.insert(entity, animation).expect("plant entity is alive");
+        }
+
+        if !changes.is_empty() {
+            sound_effects.queue.push(SoundEffect::Plant);
+        }
+    }
+}
+
+/// The level as plants spreading see it, and which plant is on which tile.
+fn spread_grid<'a>(
+    environment: &Environment,
+    entities: &Entities<'a>,
+    positions: &WriteStorage<'a, components::Position>,
+    plantae: &WriteStorage<'a, components::Plantae>,
+    solids: &WriteStorage<'a, components::Solid>,
+    fixtures: &ReadStorage<'a, components::Fixture>,
+) -> (Grid, HashMap<(i32, i32), Entity>) {
+    let mut grid = Grid::default();
+    let mut plants_at = HashMap::new();
+
+    for (&position, soil) in environment.soil.iter() {
+        let cell = if soil.water || soil.fertility <= 0.0 {
+            Cell::Barren
+        } else {
+            Cell::Open
+        };
+
+        grid.cells.insert(position, cell);
+    }
+
+    for (entity, position) in (&**entities, positions).join() {
+        let position = (position.x, position.y);
+
+        if let Some(plant) = plantae.get(entity) {
+            plants_at.insert(position, entity);
+            grid.cells.insert(
+                position,
+                Cell::Plant {
+                    species: plant.species.clone(),
+                    grown: plant.grown,
+                    solid: solids.get(entity).is_some(),
+                },
+            );
+        } else if !plants_at.contains_key(&position) {
+            let cell = if solids.get(entity).is_some() {
+                Cell::Solid
+            } else if fixtures.get(entity).is_some() {
+                continue;
+            } else {
+                Cell::Occupied
+            };
+
+            if grid.get(position) != Some(&Cell::Solid) {
+                grid.cells.insert(position, cell);
+            }
+        }
+    }
+
+    (grid, plants_at)
+}
+
+/// Moves a plant towards its next stage, returning whether it got there.
+fn grow(plant: &mut components::Plantae, kind: &Species, environment: &mut Environment, position: (i32, i32)) -> bool {
+    let turns = match kind.stage_turns(plant.stage) {
+        Some(turns) => turns,
+        None => return false,
+    };
+
+    let soil = match environment.get(position) {
+        Some(soil) => *soil,
+        None => return false,
+    };
+
+    if soil.moisture < kind.water_need || soil.sunlight < kind.light_need {
+        return false;
+    }
+
+    plant.progress += soil.fertility;
+
+    if plant.progress < turns {
+        return false;
+    }
+
+    environment.drink(position, kind.water_use);
+
+    plant.progress -= turns;
+    plant.stage += 1;
+
+    true
+}
+
+/// Once per turn, spreads moisture from water and works out which tiles
+/// are in the shade of grown plants and `Solid` objects.
+#[derive(Default)]
+pub struct SimulateEnvironment {
+    pub turn: u32,
+}
+
+impl<'a> System<'a> for SimulateEnvironment {
+    type SystemData = (
+        Read<'a, resources::TurnCounter>,
+        Write<'a, Environment>,
         ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Plantae>,
+        ReadStorage<'a, components::Solid>,
     );
 
-    fn run(&mut self, (_entity_map, _plantae, _position): Self::SystemData) {
+    fn run(&mut self, (turn_counter, mut environment, positions, plantae, solids): Self::SystemData) {
+        let new_turn = turn_counter.turns > self.turn;
+        self.turn = turn_counter.turns;
+
+        if !new_turn {
+            return;
+        }
+
+        let mut tall: HashSet<(i32, i32)> = (&positions, &plantae)
+            .join()
+            .filter(|&(_, plant)| plant.grown)
+            .map(|(position, _)| (position.x, position.y))
+            .collect();
+
+        tall.extend((&positions, &solids).join().map(|(position, _)| (position.x, position.y)));
+
+        environment.diffuse_moisture();
+        environment.cast_shade(&tall);
+    }
+}
+
+/// Runs before everything else, so the other systems see this frame's time.
+pub struct AdvanceClock;
+
+impl<'a> System<'a> for AdvanceClock {
+    type SystemData = Write<'a, resources::GameClock>;
+
+    fn run(&mut self, mut clock: Self::SystemData) {
+        clock.tick();
     }
 }
 
@@ -35,82 +302,1169 @@ impl<'a> System<'a> for PlayerMovement {
 
         for movement in (&mut movements).join() {
             if movement.player_owned {
-                if controller_state.get_button_pressed(Buttons::Up) {
-                    movement.move_queue.push_back(MoveAction::Up);
+                let action = if controller_state.get_button_pressed(Buttons::Up) {
+                    Some(MoveAction::Up)
                 } else if controller_state.get_button_pressed(Buttons::Down) {
-                    movement.move_queue.push_back(MoveAction::Down);
+                    Some(MoveAction::Down)
                 } else if controller_state.get_button_pressed(Buttons::Left) {
-                    movement.move_queue.push_back(MoveAction::Left);
+                    Some(MoveAction::Left)
                 } else if controller_state.get_button_pressed(Buttons::Right) {
-                    movement.move_queue.push_back(MoveAction::Right);
+                    Some(MoveAction::Right)
+                } else {
+                    None
+                };
+
+                if let Some(action) = action {
+                    // Taking over with the keyboard stops walking to a
+                    // clicked tile.
+                    if movement.destination.is_some() {
+                        movement.cancel();
+                    }
+
+                    movement.move_queue.push_back(action);
                 }
             }
         }
     }
 }
 
-pub struct ProcessMovement;
+/// Walks the player to the tile they clicked, one step per turn, finding
+/// the way again every step since creatures and blocks move. Gives up on
+/// destinations that can't be reached or stood on, like water, a closed
+/// door or a creature's tile, rather than bumping into them. Also works out
+/// the path to preview: the one being walked, or else the one to the tile
+/// under the mouse.
+pub struct ClickToMove;
+
+impl<'a> System<'a> for ClickToMove {
+    type SystemData = (
+        Write<'a, resources::PointerState>,
+        Write<'a, resources::PathPreview>,
+        Write<'a, Pathfinder>,
+        Read<'a, Environment>,
+        Read<'a, resources::GameClock>,
+        Entities<'a>,
+        WriteStorage<'a, components::Movement>,
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Animation>,
+        ReadStorage<'a, components::Pushable>,
+        ReadStorage<'a, components::Solid>,
+        ReadStorage<'a, components::Fixture>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut pointer,
+            mut preview,
+            mut pathfinder,
+            environment,
+            clock,
+            entities,
+            mut movements,
+            positions,
+            animations,
+            pushables,
+            solids,
+            fixtures,
+        ): Self::SystemData,
+    ) {
+        let clicked = pointer.clicked.take();
+        let occupancy = build_occupancy(&entities, &positions, &movements, &pushables, &solids, &fixtures);
+        let grid = build_path_grid(&environment, &occupancy);
+        let movers: HashSet<(i32, i32)> = (&positions, &movements)
+            .join()
+            .map(|(position, _)| (position.x, position.y))
+            .collect();
+
+        preview.tiles.clear();
+
+        for (movement, position, animation) in (&mut movements, &positions, &animations).join() {
+            if !movement.player_owned {
+                continue;
+            }
+
+            let from = (position.x, position.y);
+
+            if let Some(tile) = clicked {
+                movement.cancel();
+                movement.destination = if tile == from { None } else { Some(tile) };
+            }
+
+            let target = match movement.destination.or(pointer.hovered) {
+                Some(target) => target,
+                None => continue,
+            };
+
+            let mut blocked = movers.clone();
+            blocked.remove(&from);
+
+            let path = match pathfinder.find_avoiding(&grid, from, target, &blocked) {
+                Some(path) => path,
+                None => {
+                    movement.destination = None;
+                    continue;
+                }
+            };
+
+            let mut tile = from;
+
+            for action in path.iter() {
+                let (dx, dy) = action.direction();
+                tile = (tile.0 + dx, tile.1 + dy);
+                preview.tiles.push(tile);
+            }
+
+            if movement.destination.is_none() {
+                continue;
+            }
+
+            match path.first() {
+                Some(&action) => {
+                    let (dx, dy) = action.direction();
+
+                    // Paths only lead onto someone else's tile when they're
+                    // standing on the destination, and walking into them
+                    // would only bump.
+                    if blocked.contains(&(from.0 + dx, from.1 + dy)) {
+                        movement.destination = None;
+                    } else if movement.move_queue.is_empty() && !animation.is_moving(clock.now()) {
+                        movement.move_queue.push_back(action);
+                    }
+                }
+                None => movement.destination = None,
+            }
+        }
+    }
+}
+
+/// Moves either the player or the creatures along their queued moves, so
+/// the two can take their turns one after the other. Walking into a
+/// `Pushable` block shoves it, and any block it chains into, one tile
+/// along; the push and the step behind it are a single turn. Creatures
+/// can't push. Anything else `Solid`, another mover, or the edge of the
+/// level stops the move with a bump, which is a turn for the player but
+/// not a move.
+pub struct ProcessMovement {
+    pub player_owned: bool,
+}
 
 impl<'a> System<'a> for ProcessMovement {
     type SystemData = (
+        Entities<'a>,
         WriteStorage<'a, components::Movement>,
         WriteStorage<'a, components::Position>,
         WriteStorage<'a, components::Animation>,
+        ReadStorage<'a, components::Pushable>,
+        ReadStorage<'a, components::Solid>,
+        ReadStorage<'a, components::Fixture>,
+        ReadStorage<'a, components::Plantae>,
+        ReadStorage<'a, components::Trigger>,
+        Read<'a, Environment>,
+        Write<'a, History>,
+        Write<'a, ScriptEvents>,
+        Write<'a, resources::SoundEffects>,
+        Write<'a, resources::TurnCounter>,
+        Read<'a, resources::GameClock>,
     );
 
-    fn run(&mut self, (mut movements, mut positions, mut animations): Self::SystemData) {
-        for (movement, position, animation) in (&mut movements, &mut positions, &mut animations).join() {
-            let (target_x, target_y) = match movement.move_queue.front() {
-                Some(&MoveAction::Up) => (position.x, position.y - 1),
-                Some(&MoveAction::Down) => (position.x, position.y + 1),
-                Some(&MoveAction::Left) => (position.x - 1, position.y),
-                Some(&MoveAction::Right) => (position.x + 1, position.y),
-                _ => continue,
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut movements,
+            mut positions,
+            mut animations,
+            pushables,
+            solids,
+            fixtures,
+            plantae,
+            triggers,
+            environment,
+            mut history,
+            mut script_events,
+            mut sound_effects,
+            mut turn_counter,
+            clock,
+        ): Self::SystemData,
+    ) {
+        let player_owned = self.player_owned;
+        let moves: Vec<(Entity, MoveAction)> = (&*entities, &mut movements)
+            .join()
+            .filter(|&(_, ref movement)| movement.player_owned == player_owned)
+            .filter_map(|(entity, movement)| movement.move_queue.pop_front().map(|action| (entity, action)))
+            .collect();
+
+        let now = clock.now();
+
+        for (entity, action) in moves {
+            let direction = action.direction();
+            let target = match positions.get(entity) {
+                Some(position) => (position.x + direction.0, position.y + direction.1),
+                None => continue,
             };
 
-            movement.move_queue.pop_front();
+            let occupancy = build_occupancy(&entities, &positions, &movements, &pushables, &solids, &fixtures);
+
+            let pushed = match collision::push_chain(target, direction, &occupancy, &environment) {
+                Some(ref pushed) if !player_owned && !pushed.is_empty() => None,
+                result => result,
+            };
+
+            // Walking into a wall still takes the player's turn, it just
+            // doesn't count as a move.
+            if player_owned {
+                history.push(record_turn(&entities, &positions, &plantae, &triggers, &environment, &turn_counter));
+
+                turn_counter.turns += 1;
+            }
+
+            let pushed = match pushed {
+                Some(pushed) => pushed,
+                None => {
+                    if let Some(animation) = animations.get_mut(entity) {
+                        animation.animation_queue.push_back(AnimationEvent::Tween(Tween::bump(direction, now)));
+                    }
+
+                    sound_effects.queue.push(SoundEffect::Bump);
+                    continue;
+                }
+            };
+
+            if player_owned {
+                turn_counter.moves += 1;
+            }
+
+            for &moved in pushed.iter().rev().chain(iter::once(&entity)) {
+                slide(moved, direction, now, &mut positions, &mut animations);
+            }
 
-            animation.animation_queue.push_back(AnimationEvent::Position(components::AnimationEventPosition {
-                offset: (position.x - target_x, position.y - target_y),
-                start: Instant::now(),
-                end: Instant::now() + Duration::from_millis(100),
-            }));
+            if player_owned {
+                script_events.queue.push(ScriptEvent::PlayerEntered {
+                    x: target.0,
+                    y: target.1,
+                });
+            }
 
-            position.x = target_x;
-            position.y = target_y;
+            sound_effects.queue.push(SoundEffect::Step);
         }
     }
 }
 
-pub struct ProcessAnimation;
+/// Moves an entity one step, animating it from where it was.
+fn slide<'a>(
+    entity: Entity,
+    direction: (i32, i32),
+    now: Duration,
+    positions: &mut WriteStorage<'a, components::Position>,
+    animations: &mut WriteStorage<'a, components::Animation>,
+) {
+    let position = match positions.get_mut(entity) {
+        Some(position) => position,
+        None => return,
+    };
 
-impl<'a> System<'a> for ProcessAnimation {
-    type SystemData = WriteStorage<'a, components::Animation>;
+    position.x += direction.0;
+    position.y += direction.1;
 
-    fn run(&mut self, mut animations: Self::SystemData) {
-        let now = Instant::now();
+    if let Some(animation) = animations.get_mut(entity) {
+        animation.animation_queue.push_back(AnimationEvent::Position(components::AnimationEventPosition {
+            offset: (-direction.0, -direction.1),
+            start: now,
+            end: now + animation.move_duration,
+            easing: animation.move_easing,
+        }));
+    }
+}
 
-        for animation in (&mut animations).join() {
-            let mut should_pop = false;
+/// What stands where, for working out which moves are blocked.
+fn build_occupancy<'a, D, M>(
+    entities: &Entities<'a>,
+    positions: &Storage<'a, components::Position, D>,
+    movements: &Storage<'a, components::Movement, M>,
+    pushables: &ReadStorage<'a, components::Pushable>,
+    solids: &ReadStorage<'a, components::Solid>,
+    fixtures: &ReadStorage<'a, components::Fixture>,
+) -> Occupancy
+where
+    D: Deref<Target = MaskedStorage<components::Position>>,
+    M: Deref<Target = MaskedStorage<components::Movement>>,
+{
+    let mut occupancy = Occupancy::default();
+
+    for (entity, position) in (&**entities, positions).join() {
+        let occupant = match (pushables.get(entity), solids.get(entity)) {
+            (Some(pushable), _) => Occupant::Pushable {
+                entity,
+                chain: pushable.chain,
+            },
+            (None, Some(_)) => Occupant::Solid,
+            (None, None) if movements.get(entity).is_some() => Occupant::Mover,
+            (None, None) if fixtures.get(entity).is_some() => continue,
+            (None, None) => Occupant::Other,
+        };
+
+        occupancy.add((position.x, position.y), occupant);
+    }
+
+    occupancy
+}
+
+/// The tiles of the level paths can cross: anything but water, walls,
+/// closed doors and blocks. Other movers come and go, so stepping around
+/// them is left to whoever follows the path.
+fn build_path_grid(environment: &Environment, occupancy: &Occupancy) -> PathGrid {
+    let costs = environment
+        .soil
+        .iter()
+        .filter(|&(position, _)| match occupancy.get(*position) {
+            Some(&Occupant::Pushable { .. }) | Some(&Occupant::Solid) => false,
+            _ => true,
+        })
+        .filter_map(|(&position, soil)| pathfinding::soil_cost(soil).map(|cost| (position, cost)))
+        .collect();
+
+    PathGrid::new(costs)
+}
+
+/// The state of the level before a turn is taken, for undoing it.
+fn record_turn<'a>(
+    entities: &Entities<'a>,
+    positions: &WriteStorage<'a, components::Position>,
+    plantae: &ReadStorage<'a, components::Plantae>,
+    triggers: &ReadStorage<'a, components::Trigger>,
+    environment: &Environment,
+    turn_counter: &resources::TurnCounter,
+) -> TurnRecord {
+    let mut record = TurnRecord {
+        turns: turn_counter.turns,
+        moves: turn_counter.moves,
+        positions: Vec::new(),
+        plants: Vec::new(),
+        triggers: (&**entities, triggers)
+            .join()
+            .map(|(entity, trigger)| (entity, trigger.active))
+            .collect(),
+        environment: environment.clone(),
+    };
+
+    for (entity, position) in (&**entities, positions).join() {
+        match plantae.get(entity) {
+            Some(plant) => record.plants.push(PlantRecord {
+                position: (position.x, position.y),
+                plant: plant.clone(),
+            }),
+            None => record.positions.push((entity, (position.x, position.y))),
+        }
+    }
+
+    record
+}
+
+/// Takes back the last turn when undo is pressed. Whatever moved slides back
+/// to where it was, and the plants and soil return to how they were.
+pub struct UndoTurn;
+
+impl<'a> System<'a> for UndoTurn {
+    type SystemData = (
+        Option<Read<'a, ControllerState>>,
+        Read<'a, SpeciesTable>,
+        Read<'a, resources::GameClock>,
+        Write<'a, History>,
+        Write<'a, resources::TurnCounter>,
+        Write<'a, Environment>,
+        Write<'a, Goals>,
+        Entities<'a>,
+        WriteStorage<'a, components::Movement>,
+        WriteStorage<'a, components::Position>,
+        WriteStorage<'a, components::Animation>,
+        WriteStorage<'a, components::Plantae>,
+        WriteStorage<'a, components::Sprite>,
+        WriteStorage<'a, components::Solid>,
+        WriteStorage<'a, components::Trigger>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            controller_state,
+            species,
+            clock,
+            mut history,
+            mut turn_counter,
+            mut environment,
+            mut goals,
+            entities,
+            mut movements,
+            mut positions,
+            mut animations,
+            mut plantae,
+            mut sprites,
+            mut solids,
+            mut triggers,
+        ): Self::SystemData,
+    ) {
+        let controller_state = controller_state.expect("no controller state");
+
+        if !controller_state.get_button_pressed(Buttons::Undo) {
+            return;
+        }
+
+        let record = match history.pop() {
+            Some(record) => record,
+            None => return,
+        };
+
+        turn_counter.turns = record.turns;
+        turn_counter.moves = record.moves;
+        *environment = record.environment;
+        // The turn we're back at was already checked.
+        goals.evaluated_turn = Some(record.turns);
+
+        for movement in (&mut movements).join() {
+            movement.cancel();
+        }
+
+        let now = clock.now();
+
+        for &(entity, (x, y)) in record.positions.iter() {
+            let position = match positions.get_mut(entity) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            if (position.x, position.y) == (x, y) {
+                continue;
+            }
+
+            if let Some(animation) = animations.get_mut(entity) {
+                animation.animation_queue.push_back(AnimationEvent::Position(components::AnimationEventPosition {
+                    offset: (position.x - x, position.y - y),
+                    start: now,
+                    end: now + animation.move_duration,
+                    easing: animation.move_easing,
+                }));
+            }
+
+            position.x = x;
+            position.y = y;
+        }
+
+        for &(entity, active) in record.triggers.iter() {
+            if let Some(trigger) = triggers.get_mut(entity) {
+                trigger.active = active;
+            }
+        }
+
+        let current: Vec<Entity> = (&*entities, &plantae).join().map(|(entity, _)| entity).collect();
+
+        for entity in current {
+            // Like eaten plants, undone ones are taken away right away so
+            // triggers and goals later this frame don't still see them.
+            plantae.remove(entity);
+            solids.remove(entity);
+            entities.delete(entity).expect("plant entity is alive");
+        }
+
+        for plant in record.plants.iter() {
+            let kind = match species.get(&plant.plant.species) {
+                Some(kind) => kind,
+                None => continue,
+            };
+
+            let entity = entities.create();
+            let (x, y) = plant.position;
+
+            positions.insert(entity, components::Position::new(x, y)).expect("plant entity is alive");
+            sprites
+                .insert(entity, components::Sprite { tile: kind.tile(plant.plant.stage), z: 0 })
+                .expect("plant entity is alive");
+            animations.insert(entity, components::Animation::new()).expect("plant entity is alive");
+            plantae.insert(entity, plant.plant.clone()).expect("plant entity is alive");
+
+            if plant.plant.grown && kind.solid_when_grown {
+                solids.insert(entity, components::Solid).expect("plant entity is alive");
+            }
+        }
+    }
+}
+
+/// Once the player has taken a turn, queues the next move of every creature
+/// according to its behaviour. Creatures decide one after another, each
+/// keeping clear of the tiles the ones before it are heading for.
+#[derive(Default)]
+pub struct CreatureAi {
+    pub turn: u32,
+}
+
+impl<'a> System<'a> for CreatureAi {
+    type SystemData = (
+        Read<'a, resources::TurnCounter>,
+        Read<'a, Environment>,
+        Write<'a, Pathfinder>,
+        Entities<'a>,
+        WriteStorage<'a, components::Creature>,
+        WriteStorage<'a, components::Movement>,
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Pushable>,
+        ReadStorage<'a, components::Solid>,
+        ReadStorage<'a, components::Fixture>,
+        ReadStorage<'a, components::Plantae>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            turn_counter,
+            environment,
+            mut pathfinder,
+            entities,
+            mut creatures,
+            mut movements,
+            positions,
+            pushables,
+            solids,
+            fixtures,
+            plantae,
+        ): Self::SystemData,
+    ) {
+        let new_turn = turn_counter.turns > self.turn;
+        self.turn = turn_counter.turns;
+
+        if !new_turn {
+            return;
+        }
 
-            {
-                let animation_event = animation.animation_queue.front();
+        let occupancy = build_occupancy(&entities, &positions, &movements, &pushables, &solids, &fixtures);
+        let grid = build_path_grid(&environment, &occupancy);
 
-                match animation_event {
-                    Some(&AnimationEvent::Position(ref position_event)) => {
-                        let components::AnimationEventPosition { end, .. } = position_event;
+        let mut movers: HashSet<(i32, i32)> = (&positions, &movements)
+            .join()
+            .map(|(position, _)| (position.x, position.y))
+            .collect();
 
-                        if now >= *end {
-                            should_pop = true;
+        let player = (&positions, &movements)
+            .join()
+            .find(|&(_, movement)| movement.player_owned)
+            .map(|(position, _)| (position.x, position.y));
+
+        let plants: Vec<(i32, i32)> = (&positions, &plantae)
+            .join()
+            .map(|(position, _)| (position.x, position.y))
+            .collect();
+
+        for (entity, creature, movement, position) in (&*entities, &mut creatures, &mut movements, &positions).join() {
+            let from = (position.x, position.y);
+
+            let action = {
+                let view = ai::View {
+                    grid: &grid,
+                    movers: &movers,
+                    player,
+                    plants: &plants,
+                };
+
+                ai::choose_move(
+                    &creature.behaviour,
+                    &mut creature.waypoint,
+                    from,
+                    &view,
+                    &mut pathfinder,
+                    turn_counter.turns ^ entity.id().rotate_left(16),
+                )
+            };
+
+            if let Some(action) = action {
+                let (dx, dy) = action.direction();
+
+                movers.remove(&from);
+                movers.insert((from.0 + dx, from.1 + dy));
+                movement.move_queue.push_back(action);
+            }
+        }
+    }
+}
+
+/// Hungry creatures eat whatever plant they end up standing on.
+pub struct EatPlants;
+
+impl<'a> System<'a> for EatPlants {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, components::Creature>,
+        ReadStorage<'a, components::Position>,
+        WriteStorage<'a, components::Plantae>,
+    );
+
+    fn run(&mut self, (entities, creatures, positions, mut plantae): Self::SystemData) {
+        let grazing: HashSet<(i32, i32)> = (&creatures, &positions)
+            .join()
+            .filter(|&(creature, _)| creature.hungry)
+            .map(|(_, position)| (position.x, position.y))
+            .collect();
+
+        if grazing.is_empty() {
+            return;
+        }
+
+        let eaten: Vec<Entity> = (&*entities, &positions, &plantae)
+            .join()
+            .filter(|&(_, position, _)| grazing.contains(&(position.x, position.y)))
+            .map(|(entity, _, _)| entity)
+            .collect();
+
+        for entity in eaten {
+            // Taking the plant away right away keeps the systems after this
+            // one from seeing it before the world is maintained.
+            plantae.remove(entity);
+            entities.delete(entity).expect("plant entity is alive");
+        }
+    }
+}
+
+/// Reads the triggers whenever a turn has passed or been undone, and opens
+/// or closes the reactors on their channels. A reactor with something
+/// standing on it stays as it is, so a door never shuts on the player.
+/// Bridges and water gates set the ground under them every frame, so it
+/// stays right after an undo or a reload of the level.
+#[derive(Default)]
+pub struct Mechanisms {
+    pub turn: Option<u32>,
+}
+
+impl<'a> System<'a> for Mechanisms {
+    type SystemData = (
+        Read<'a, resources::TurnCounter>,
+        Write<'a, Environment>,
+        Entities<'a>,
+        WriteStorage<'a, components::Trigger>,
+        WriteStorage<'a, components::Reactor>,
+        WriteStorage<'a, components::Sprite>,
+        WriteStorage<'a, components::Solid>,
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Movement>,
+        ReadStorage<'a, components::Pushable>,
+        ReadStorage<'a, components::Plantae>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            turn_counter,
+            mut environment,
+            entities,
+            mut triggers,
+            mut reactors,
+            mut sprites,
+            mut solids,
+            positions,
+            movements,
+            pushables,
+            plantae,
+        ): Self::SystemData,
+    ) {
+        let forward = self.turn.map_or(false, |turn| turn_counter.turns > turn);
+        let changed = self.turn != Some(turn_counter.turns);
+        self.turn = Some(turn_counter.turns);
+
+        if changed {
+            let player = (&positions, &movements)
+                .join()
+                .find(|&(_, movement)| movement.player_owned)
+                .map(|(position, _)| (position.x, position.y));
+
+            let mut weighed: HashSet<(i32, i32)> = (&positions, &movements)
+                .join()
+                .map(|(position, _)| (position.x, position.y))
+                .collect();
+
+            weighed.extend((&positions, &pushables).join().map(|(position, _)| (position.x, position.y)));
+
+            let grown: HashSet<(i32, i32)> = (&positions, &plantae)
+                .join()
+                .filter(|&(_, plant)| plant.grown)
+                .map(|(position, _)| (position.x, position.y))
+                .collect();
+
+            for (trigger, position, sprite) in (&mut triggers, &positions, &mut sprites).join() {
+                let tile = (position.x, position.y);
+
+                match trigger.kind {
+                    TriggerKind::PressurePlate => {
+                        trigger.occupied = weighed.contains(&tile);
+                        trigger.active = trigger.occupied;
+                    }
+                    TriggerKind::Lever => {
+                        let occupied = player == Some(tile);
+
+                        // Undone turns put the lever back through the
+                        // history instead.
+                        if forward && occupied && !trigger.occupied {
+                            trigger.active = !trigger.active;
                         }
+
+                        trigger.occupied = occupied;
                     }
-                    _ => continue,
-                };
+                    TriggerKind::PlantGrown => {
+                        trigger.occupied = grown.contains(&tile);
+                        trigger.active = trigger.occupied;
+                    }
+                }
+
+                sprite.tile.sprite_id = trigger.kind.sprite_id(trigger.active);
+            }
+
+            let states: Vec<(u32, bool)> = (&triggers)
+                .join()
+                .map(|trigger| (trigger.channel, trigger.active))
+                .collect();
+            let signals = mechanisms::channel_signals(&states);
+
+            for (entity, reactor, position, sprite) in (&*entities, &mut reactors, &positions, &mut sprites).join() {
+                let mut open = mechanisms::is_open(&signals, reactor.channel, reactor.inverted);
+
+                if let Some(current) = reactor.open {
+                    if current != open && weighed.contains(&(position.x, position.y)) {
+                        open = current;
+                    }
+                }
+
+                if reactor.open == Some(open) {
+                    continue;
+                }
+
+                reactor.open = Some(open);
+                sprite.tile.sprite_id = reactor.kind.sprite_id(open);
+
+                if reactor.kind.is_solid(open) {
+                    solids.insert(entity, components::Solid).expect("reactor entity is alive");
+                } else {
+                    solids.remove(entity);
+                }
             }
+        }
+
+        for (reactor, position) in (&reactors, &positions).join() {
+            let open = reactor.open.unwrap_or(false);
+            let soil = match environment.get_mut((position.x, position.y)) {
+                Some(soil) => soil,
+                None => continue,
+            };
+
+            match reactor.kind {
+                ReactorKind::Door => {}
+                ReactorKind::Bridge => soil.bridged = open,
+                ReactorKind::WaterGate => {
+                    soil.water = open || soil.terrain == Terrain::Water;
+
+                    if open {
+                        soil.moisture = SATURATED;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Checks the level's objectives whenever a turn has passed, raising a
+/// `LevelEvent` the first time the level is completed or failed.
+pub struct EvaluateGoals;
+
+impl<'a> System<'a> for EvaluateGoals {
+    type SystemData = (
+        Read<'a, resources::TurnCounter>,
+        Read<'a, SpeciesTable>,
+        Write<'a, Goals>,
+        Write<'a, LevelEvents>,
+        ReadStorage<'a, components::Position>,
+        ReadStorage<'a, components::Movement>,
+        ReadStorage<'a, components::Plantae>,
+    );
+
+    fn run(
+        &mut self,
+        (turn_counter, species, mut goals, mut level_events, positions, movements, plantae): Self::SystemData,
+    ) {
+        let finished = goals.status.map_or(false, |status| status != GoalStatus::InProgress);
+
+        if finished || goals.evaluated_turn == Some(turn_counter.turns) {
+            return;
+        }
+
+        let player = (&positions, &movements)
+            .join()
+            .find(|&(_, movement)| movement.player_owned)
+            .map(|(position, _)| (position.x, position.y));
+
+        let grown: HashSet<(i32, i32)> = (&positions, &plantae)
+            .join()
+            .filter(|&(_, plant)| plant.grown)
+            .map(|(position, _)| (position.x, position.y))
+            .collect();
+
+        let flowers = (&plantae)
+            .join()
+            .filter(|plant| plant.grown && species.get(&plant.species).map_or(false, |species| species.flower))
+            .count();
+
+        let snapshot = goals::Snapshot {
+            moves: turn_counter.moves,
+            turns: turn_counter.turns,
+            player,
+            grown: &grown,
+            flowers: flowers as u32,
+        };
+
+        let status = goals::goal_status(&goals.objectives, &snapshot);
+
+        goals.evaluated_turn = Some(turn_counter.turns);
+        goals.status = Some(status);
+
+        match status {
+            GoalStatus::Complete => level_events.queue.push(LevelEvent::Complete {
+                moves: turn_counter.moves,
+                stars: goals::star_rating(turn_counter.moves, goals.par),
+            }),
+            GoalStatus::Failed(limit) => level_events.queue.push(LevelEvent::Failed { limit }),
+            GoalStatus::InProgress => {}
+        }
+    }
+}
+
+pub struct ProcessAnimation;
+
+impl<'a> System<'a> for ProcessAnimation {
+    type SystemData = (
+        WriteStorage<'a, components::Animation>,
+        Read<'a, resources::GameClock>,
+    );
+
+    fn run(&mut self, (mut animations, clock): Self::SystemData) {
+        let now = clock.now();
+
+        for animation in (&mut animations).join() {
+            animation.animation_queue.retain(|event| now < event.end());
+        }
+    }
+}
 
-            if should_pop {
-                animation.animation_queue.pop_front();
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use specs::RunNow;
+
+    use super::*;
+    use audio::{Audio, NullBackend};
+    use entities;
+    use environment::Soil;
+    use level::Terrain;
+    use settings::Settings;
+    use tween::TweenState;
+    use world::World;
+
+    /// A row of grass from (0, 0) to (4, 0) ending in water at (5, 0), with
+    /// the player at (1, 0), and the resources the turn systems need.
+    fn test_world() -> (World, Entity) {
+        let mut world = World::new();
+        let mut environment = Environment::default();
+
+        for x in 0..5 {
+            environment.soil.insert((x, 0), Soil::new(Terrain::Grass));
+        }
+
+        environment.soil.insert((5, 0), Soil::new(Terrain::Water));
+
+        world.specs_world.add_resource(environment);
+        world.specs_world.add_resource(History::default());
+        world.specs_world.add_resource(Pathfinder::default());
+        world.specs_world.add_resource(resources::PathPreview::default());
+        world.specs_world.add_resource(resources::PointerState::default());
+        world.specs_world.add_resource(ScriptEvents::default());
+        world.specs_world.add_resource(resources::SoundEffects::default());
+        world.specs_world.add_resource(resources::TurnCounter::default());
+        world
+            .specs_world
+            .add_resource(resources::GameClock::new(Duration::from_millis(10)));
+
+        let player = entities::create_player(&mut world, 1, 0);
+
+        (world, player)
+    }
+
+    fn take_turn(world: &mut World, player: Entity, action: MoveAction) {
+        world
+            .specs_world
+            .write_storage::<components::Movement>()
+            .get_mut(player)
+            .expect("player can move")
+            .move_queue
+            .push_back(action);
+
+        ProcessMovement { player_owned: true }.run_now(&world.specs_world.res);
+    }
+
+    fn position(world: &World, entity: Entity) -> (i32, i32) {
+        let positions = world.specs_world.read_storage::<components::Position>();
+        let position = positions.get(entity).expect("entity has a position");
+
+        (position.x, position.y)
+    }
+
+    /// Plays this frame's sounds like the gameplay scene does, through a
+    /// backend that only records them.
+    fn played_sounds(world: &World) -> Vec<String> {
+        let backend = NullBackend::new();
+        let played = backend.played();
+        let mut audio = Audio::new(Box::new(backend), &Settings::default());
+
+        for effect in world.specs_world.write_resource::<resources::SoundEffects>().queue.drain(..) {
+            audio.play(effect);
+        }
+
+        let sounds = played.borrow().clone();
+        sounds
+    }
+
+    fn run_frames(world: &mut World, frames: u32) {
+        for _ in 0..frames {
+            AdvanceClock.run_now(&world.specs_world.res);
+            ProcessAnimation.run_now(&world.specs_world.res);
+        }
+    }
+
+    /// Whether the entity is still sliding, and how far from its tile it's
+    /// drawn, combining its tweens like the gameplay scene does.
+    fn animation_progress(world: &World, entity: Entity) -> (bool, (f32, f32)) {
+        let now = world.specs_world.read_resource::<resources::GameClock>().now();
+        let animations = world.specs_world.read_storage::<components::Animation>();
+        let animation = animations.get(entity).expect("entity is animated");
+        let mut state = TweenState::default();
+
+        for event in animation.animation_queue.iter() {
+            match *event {
+                AnimationEvent::Position(ref position) => state.apply(&position.tween(), now),
+                AnimationEvent::Tween(ref tween) => state.apply(tween, now),
             }
         }
+
+        (animation.is_moving(now), state.offset)
+    }
+
+    #[test]
+    fn a_move_finishes_after_its_duration() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Right);
+
+        // Halfway through the player's 120ms slide, on a 10ms step.
+        run_frames(&mut world, 6);
+        let (moving, offset) = animation_progress(&world, player);
+
+        assert!(moving);
+        assert!(offset.0 > -1.0 && offset.0 < 0.0);
+        assert_eq!(offset.1, 0.0);
+
+        run_frames(&mut world, 6);
+
+        assert_eq!(animation_progress(&world, player), (false, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn a_paused_clock_holds_a_move_until_stepped() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Right);
+        world.specs_world.write_resource::<resources::GameClock>().paused = true;
+        run_frames(&mut world, 30);
+
+        assert_eq!(animation_progress(&world, player), (true, (-1.0, 0.0)));
+
+        world.specs_world.write_resource::<resources::GameClock>().step_frames(12);
+        run_frames(&mut world, 30);
+
+        assert_eq!(animation_progress(&world, player), (false, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn a_move_plays_a_step() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Right);
+
+        assert_eq!(position(&world, player), (2, 0));
+        assert_eq!(played_sounds(&world), vec![SoundEffect::Step.name().to_string()]);
+    }
+
+    #[test]
+    fn a_blocked_move_plays_a_bump() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Up);
+
+        assert_eq!(position(&world, player), (1, 0));
+        assert_eq!(played_sounds(&world), vec![SoundEffect::Bump.name().to_string()]);
+    }
+
+    fn turns_and_moves(world: &World) -> (u32, u32) {
+        let turn_counter = world.specs_world.read_resource::<resources::TurnCounter>();
+
+        (turn_counter.turns, turn_counter.moves)
+    }
+
+    #[test]
+    fn a_bump_takes_a_turn_but_not_a_move() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Right);
+        take_turn(&mut world, player, MoveAction::Up);
+
+        assert_eq!(turns_and_moves(&world), (2, 1));
+        assert_eq!(world.specs_world.read_resource::<History>().len(), 2);
+    }
+
+    #[test]
+    fn the_player_bumps_into_creatures() {
+        let (mut world, player) = test_world();
+        entities::create_slime(&mut world, 2, 0, ai::Behaviour::Wander, false);
+
+        take_turn(&mut world, player, MoveAction::Right);
+
+        assert_eq!(position(&world, player), (1, 0));
+        assert_eq!(played_sounds(&world), vec![SoundEffect::Bump.name().to_string()]);
+    }
+
+    fn click(world: &mut World, tile: (i32, i32)) {
+        world.specs_world.write_resource::<resources::PointerState>().clicked = Some(tile);
+        ClickToMove.run_now(&world.specs_world.res);
+    }
+
+    /// The player's destination and the moves queued up to get there.
+    fn walking(world: &World, player: Entity) -> (Option<(i32, i32)>, Vec<MoveAction>) {
+        let movements = world.specs_world.read_storage::<components::Movement>();
+        let movement = movements.get(player).expect("player can move");
+
+        (movement.destination, movement.move_queue.iter().cloned().collect())
+    }
+
+    #[test]
+    fn clicking_a_tile_walks_towards_it() {
+        let (mut world, player) = test_world();
+
+        click(&mut world, (3, 0));
+
+        assert_eq!(walking(&world, player), (Some((3, 0)), vec![MoveAction::Right]));
+    }
+
+    #[test]
+    fn clicking_water_or_the_edge_of_the_level_goes_nowhere() {
+        let (mut world, player) = test_world();
+
+        click(&mut world, (5, 0));
+        assert_eq!(walking(&world, player), (None, vec![]));
+
+        click(&mut world, (1, 1));
+        assert_eq!(walking(&world, player), (None, vec![]));
+    }
+
+    #[test]
+    fn walking_to_a_creature_stops_next_to_it() {
+        let (mut world, player) = test_world();
+        entities::create_slime(&mut world, 3, 0, ai::Behaviour::Wander, false);
+
+        click(&mut world, (3, 0));
+        ProcessMovement { player_owned: true }.run_now(&world.specs_world.res);
+        run_frames(&mut world, 12);
+        ClickToMove.run_now(&world.specs_world.res);
+
+        assert_eq!(position(&world, player), (2, 0));
+        assert_eq!(walking(&world, player), (None, vec![]));
+        assert_eq!(played_sounds(&world), vec![SoundEffect::Step.name().to_string()]);
+    }
+
+    #[test]
+    fn a_bump_holds_the_next_step_until_it_has_played() {
+        let (mut world, player) = test_world();
+
+        take_turn(&mut world, player, MoveAction::Up);
+        assert!(animation_progress(&world, player).0);
+
+        // Bumps take 150ms on a 10ms step.
+        run_frames(&mut world, 15);
+        assert!(!animation_progress(&world, player).0);
+    }
+
+    fn daisy() -> Species {
+        Species {
+            name: "daisy".to_string(),
+            stages: Vec::new(),
+            water_need: 0.0,
+            water_use: 0.0,
+            light_need: 0.0,
+            spread: Vec::new(),
+            solid_when_grown: false,
+            flower: true,
+        }
+    }
+
+    fn undo(world: &mut World) {
+        let mut controller_state = ControllerState::new();
+        controller_state.button_down(Buttons::Undo);
+
+        world.specs_world.add_resource(controller_state);
+        UndoTurn.run_now(&world.specs_world.res);
+    }
+
+    fn is_solid(world: &World, entity: Entity) -> bool {
+        world.specs_world.read_storage::<components::Solid>().get(entity).is_some()
+    }
+
+    #[test]
+    fn undoing_a_push_puts_the_block_back_too() {
+        let (mut world, player) = test_world();
+        world.specs_world.add_resource(SpeciesTable::default());
+        world.specs_world.add_resource(Goals::default());
+
+        let block = entities::create_block(&mut world, 2, 0, false);
+
+        take_turn(&mut world, player, MoveAction::Right);
+        assert_eq!(position(&world, player), (2, 0));
+        assert_eq!(position(&world, block), (3, 0));
+
+        undo(&mut world);
+        assert_eq!(position(&world, player), (1, 0));
+        assert_eq!(position(&world, block), (2, 0));
+    }
+
+    #[test]
+    fn undoing_a_grown_plant_shuts_its_reactor() {
+        let (mut world, player) = test_world();
+        world.specs_world.add_resource(SpeciesTable::new(vec![daisy()], 0));
+        world.specs_world.add_resource(Goals::default());
+
+        entities::create_trigger(&mut world, 3, 0, TriggerKind::PlantGrown, 1);
+        let door = entities::create_reactor(&mut world, 4, 0, ReactorKind::Door, 1, false);
+        let mut mechanisms = Mechanisms::default();
+
+        mechanisms.run_now(&world.specs_world.res);
+        assert!(is_solid(&world, door));
+
+        take_turn(&mut world, player, MoveAction::Left);
+        let plant = entities::create_plant(&mut world, 3, 0, &daisy());
+        world
+            .specs_world
+            .write_storage::<components::Plantae>()
+            .get_mut(plant)
+            .expect("plant is alive")
+            .grown = true;
+
+        mechanisms.run_now(&world.specs_world.res);
+        assert!(!is_solid(&world, door));
+
+        // Undo and the mechanisms run in the same frame, before the world
+        // is maintained.
+        undo(&mut world);
+        mechanisms.run_now(&world.specs_world.res);
+        assert!(is_solid(&world, door));
     }
 }
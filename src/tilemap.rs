@@ -1,103 +1,554 @@
-use ggez::graphics::spritebatch::{SpriteBatch, SpriteIdx};
-use ggez::graphics::{Color, DrawParam, Point2, Rect, FilterMode};
-use ggez::Context;
+use std::collections::HashMap;
+
+use ggez::graphics::spritebatch::SpriteBatch;
+use ggez::graphics::{self, Color, DrawParam, FilterMode, Point2};
+use ggez::{Context, GameError, GameResult};
+use image::{Rgba, RgbaImage};
 use warmy;
 use warmy::load::Store;
 
-use assets::Image;
+use animation::AnimationClip;
+use aseprite::Aseprite;
+use assets::{self, AssetError, Pixels};
+use atlas::{Atlas, AtlasBuilder, AtlasRegion};
 use screen::Screen;
+use tween::TweenState;
+
+pub const TILESET_INDEX_PATH: &str = "/tilesets.toml";
+
+/// Size of a grid cell in logical pixels.
+pub const TILE_SIZE: u32 = 32;
 
-#[derive(Debug, Hash, Eq, PartialEq)]
+pub const BACKGROUND_LAYER: i32 = 0;
+pub const SHADOW_LAYER: i32 = 1;
+pub const ENTITY_LAYER: i32 = 2;
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Tile {
     pub sprite_layer: i32,
+    pub tileset: String,
     pub sprite_id: i32,
 }
 
+impl Tile {
+    pub fn new(sprite_layer: i32, tileset: &str, sprite_id: i32) -> Tile {
+        Tile {
+            sprite_layer,
+            tileset: tileset.to_string(),
+            sprite_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileSetEntry {
+    pub name: String,
+    pub path: String,
+    /// Required for images; `.aseprite` files already know their frames.
+    #[serde(default)]
+    pub frame_width: Option<u32>,
+    #[serde(default)]
+    pub frame_height: Option<u32>,
+    /// Multiplies the frame size when drawn, for art that isn't drawn at the
+    /// grid's resolution.
+    #[serde(default = "default_tileset_scale")]
+    pub scale: f32,
+}
+
+fn default_tileset_scale() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TileSetIndex {
+    pub tilesets: Vec<TileSetEntry>,
+    #[serde(skip)]
+    pub generation: usize,
+}
+
+impl warmy::Load<Context> for TileSetIndex {
+    type Key = warmy::FSKey;
+    type Error = AssetError;
+
+    fn load(
+        key: Self::Key,
+        store: &mut warmy::Storage<Context>,
+        ctx: &mut Context,
+    ) -> Result<warmy::Loaded<Self>, Self::Error> {
+        let mut index: TileSetIndex = assets::read_toml(&key, store.root(), ctx)?;
+        index.generation = assets::next_generation();
+
+        Ok(warmy::Loaded::from(index))
+    }
+}
+
 #[derive(Clone)]
-pub struct TileMap {
-    sprite_dimensions: u32,
-    tile_width: u32,
-    tile_height: u32,
-    num_tiles_x: u32,
-    num_tiles_y: u32,
-    scale: Point2,
-    screen: Screen,
-    image: warmy::Res<Image>,
+enum TileSetSource {
+    Image(warmy::Res<Pixels>),
+    Aseprite(warmy::Res<Aseprite>),
+    /// The file couldn't be loaded; every frame is the missing texture.
+    Missing,
 }
 
-impl TileMap {
+/// A sprite sheet cut into equally sized frames, numbered left to right,
+/// top to bottom, or the frames of an Aseprite file in order. The frames
+/// themselves live in the sprite atlas.
+#[derive(Clone)]
+pub struct TileSet {
+    frame_width: u32,
+    frame_height: u32,
+    columns: u32,
+    rows: u32,
+    scale: f32,
+    source: TileSetSource,
+    clips: Vec<AnimationClip>,
+    frames: Vec<AtlasRegion>,
+}
+
+impl TileSet {
     pub fn new(
-        path: &str,
-        screen: Screen,
+        entry: &TileSetEntry,
         asset_store: &mut Store<Context>,
         ctx: &mut Context,
-        sprite_dimensions: u32,
-    ) -> Self {
-        let image = asset_store
-            .get::<_, Image>(&warmy::FSKey::new(path), ctx)
-            .unwrap();
-        let inner = image.borrow().0.clone();
-
-        let num_tiles_x: u32 = (screen.screen_w / sprite_dimensions) + 1;
-        let num_tiles_y: u32 = (screen.screen_h / sprite_dimensions) + 1;
-
-        TileMap {
-            sprite_dimensions,
-            tile_width: inner.width() / sprite_dimensions,
-            tile_height: inner.height() / sprite_dimensions,
-            num_tiles_x,
-            num_tiles_y,
-            scale: Point2::new(screen.scale_w, screen.scale_h),
-            screen,
-            image,
+    ) -> GameResult<TileSet> {
+        if entry.path.ends_with(".aseprite") {
+            let aseprite = assets::get::<Aseprite>(asset_store, &entry.path, ctx)?;
+
+            let (width, height, frame_count, clips) = {
+                let file = aseprite.borrow();
+                (file.width, file.height, file.frames.len() as u32, file.clips.clone())
+            };
+
+            return Ok(TileSet {
+                frame_width: width,
+                frame_height: height,
+                columns: frame_count.max(1),
+                rows: 1,
+                scale: entry.scale,
+                source: TileSetSource::Aseprite(aseprite),
+                clips,
+                frames: Vec::new(),
+            });
+        }
+
+        let (frame_width, frame_height) = match (entry.frame_width, entry.frame_height) {
+            (Some(frame_width), Some(frame_height)) => (frame_width, frame_height),
+            _ => {
+                return Err(GameError::ResourceLoadError(format!(
+                    "Tileset {} needs a frame_width and frame_height",
+                    entry.name
+                )))
+            }
+        };
+
+        let pixels = assets::get::<Pixels>(asset_store, &entry.path, ctx)?;
+
+        let (width, height) = pixels.borrow().image.dimensions();
+
+        Ok(TileSet {
+            frame_width,
+            frame_height,
+            columns: (width / frame_width).max(1),
+            rows: (height / frame_height).max(1),
+            scale: entry.scale,
+            source: TileSetSource::Image(pixels),
+            clips: Vec::new(),
+            frames: Vec::new(),
+        })
+    }
+
+    /// Stands in for a tileset whose file failed to load, so sprites using
+    /// it show up as the missing texture instead of taking the game down.
+    pub fn placeholder(entry: &TileSetEntry) -> TileSet {
+        TileSet {
+            frame_width: entry.frame_width.unwrap_or(TILE_SIZE),
+            frame_height: entry.frame_height.unwrap_or(TILE_SIZE),
+            columns: 1,
+            rows: 1,
+            scale: entry.scale,
+            source: TileSetSource::Missing,
+            clips: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.columns * self.rows
+    }
+
+    /// One image per frame, to be packed into the atlas.
+    pub fn frame_images(&self) -> Vec<RgbaImage> {
+        let pixels = match self.source {
+            TileSetSource::Aseprite(ref aseprite) => {
+                return aseprite.borrow().frames.iter().map(|frame| frame.image.clone()).collect();
+            }
+            TileSetSource::Image(ref pixels) => pixels.borrow(),
+            TileSetSource::Missing => {
+                return vec![assets::missing_texture_pixels(self.frame_width, self.frame_height)];
+            }
+        };
+        let pixels = &pixels.image;
+
+        (0..self.frame_count())
+            .map(|frame| {
+                let left = frame % self.columns * self.frame_width;
+                let top = frame / self.columns * self.frame_height;
+
+                RgbaImage::from_fn(self.frame_width, self.frame_height, |x, y| {
+                    if left + x < pixels.width() && top + y < pixels.height() {
+                        *pixels.get_pixel(left + x, top + y)
+                    } else {
+                        Rgba([0, 0, 0, 0])
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Changes whenever warmy reloads the file behind this tileset.
+    pub fn generation(&self) -> usize {
+        match self.source {
+            TileSetSource::Image(ref pixels) => pixels.borrow().generation,
+            TileSetSource::Aseprite(ref aseprite) => aseprite.borrow().generation,
+            TileSetSource::Missing => 0,
+        }
+    }
+
+    /// An animation clip by name, from the tags of an Aseprite file.
+    pub fn clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    pub fn region(&self, sprite_id: i32) -> Option<&AtlasRegion> {
+        if self.frames.is_empty() {
+            return None;
+        }
+
+        self.frames.get(sprite_id as usize % self.frames.len())
+    }
+
+    /// Drawn size of a frame in logical pixels.
+    pub fn frame_size(&self) -> (f32, f32) {
+        (
+            self.frame_width as f32 * self.scale,
+            self.frame_height as f32 * self.scale,
+        )
+    }
+}
+
+/// Name of a tileset frame's region in the atlas, e.g. `grass/3`.
+pub fn frame_region_name(tileset: &str, sprite_id: u32) -> String {
+    format!("{}/{}", tileset, sprite_id)
+}
+
+pub struct TileSetRegistry {
+    index: Option<warmy::Res<TileSetIndex>>,
+    tilesets: HashMap<String, TileSet>,
+    atlas: Atlas,
+    index_generation: usize,
+    /// Generation of each tileset's file when the atlas was last built.
+    source_generations: HashMap<String, usize>,
+    generation: usize,
+    /// Tilesets that failed to load and were drawn with a placeholder
+    /// instead, until `Assets` takes them to be shown.
+    pub errors: Vec<String>,
+}
+
+impl TileSetRegistry {
+    /// A registry without any tilesets, until the real one is loaded.
+    pub fn empty() -> TileSetRegistry {
+        TileSetRegistry {
+            index: None,
+            tilesets: HashMap::new(),
+            atlas: Atlas::empty(),
+            index_generation: 0,
+            source_generations: HashMap::new(),
+            generation: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Loads every tileset listed in the tileset index and packs all of
+    /// their frames into the atlas.
+    pub fn load(asset_store: &mut Store<Context>, ctx: &mut Context) -> GameResult<TileSetRegistry> {
+        let index = assets::get::<TileSetIndex>(asset_store, TILESET_INDEX_PATH, ctx)?;
+
+        let mut errors = Vec::new();
+        let tilesets = TileSetRegistry::load_tilesets(&index.borrow(), asset_store, ctx, &mut errors);
+        let index_generation = index.borrow().generation;
+
+        let mut registry = TileSetRegistry {
+            index: Some(index),
+            tilesets,
+            atlas: Atlas::empty(),
+            index_generation,
+            source_generations: HashMap::new(),
+            generation: 0,
+            errors,
+        };
+
+        registry.build_atlas(ctx)?;
+
+        Ok(registry)
+    }
+
+    fn load_tilesets(
+        index: &TileSetIndex,
+        asset_store: &mut Store<Context>,
+        ctx: &mut Context,
+        errors: &mut Vec<String>,
+    ) -> HashMap<String, TileSet> {
+        let mut tilesets = HashMap::new();
+
+        for entry in index.tilesets.iter() {
+            let tileset = TileSet::new(entry, asset_store, ctx).unwrap_or_else(|e| {
+                errors.push(format!("Tileset {}: {}", entry.name, e));
+                TileSet::placeholder(entry)
+            });
+
+            tilesets.insert(entry.name.clone(), tileset);
+        }
+
+        tilesets
+    }
+
+    fn build_atlas(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let mut builder = AtlasBuilder::new();
+
+        for (name, tileset) in self.tilesets.iter() {
+            for (frame, image) in tileset.frame_images().into_iter().enumerate() {
+                builder.add(&frame_region_name(name, frame as u32), image);
+            }
+        }
+
+        let atlas = builder.build(ctx)?;
+
+        for (name, tileset) in self.tilesets.iter_mut() {
+            tileset.frames = (0..tileset.frame_count())
+                .filter_map(|frame| atlas.region(&frame_region_name(name, frame)).cloned())
+                .collect();
+        }
+
+        self.source_generations = self.tilesets
+            .iter()
+            .map(|(name, tileset)| (name.clone(), tileset.generation()))
+            .collect();
+        self.atlas = atlas;
+        self.generation = assets::next_generation();
+
+        Ok(())
+    }
+
+    /// Rebuilds the atlas if the tileset index or any of the files it lists
+    /// were reloaded. Call after syncing the asset store.
+    pub fn sync(&mut self, asset_store: &mut Store<Context>, ctx: &mut Context) -> GameResult<()> {
+        let index = match self.index {
+            Some(ref index) => index.clone(),
+            None => return Ok(()),
+        };
+        let index_generation = index.borrow().generation;
+
+        if index_generation != self.index_generation {
+            self.index_generation = index_generation;
+            self.tilesets = TileSetRegistry::load_tilesets(&index.borrow(), asset_store, ctx, &mut self.errors);
+
+            return self.build_atlas(ctx);
+        }
+
+        let changed = self.tilesets
+            .iter()
+            .any(|(name, tileset)| self.source_generations.get(name) != Some(&tileset.generation()));
+
+        if changed {
+            self.build_atlas(ctx)?;
         }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TileSet> {
+        self.tilesets.get(name)
+    }
+
+    pub fn atlas(&self) -> &Atlas {
+        &self.atlas
+    }
+
+    /// Changes whenever the atlas is rebuilt.
+    pub fn generation(&self) -> usize {
+        self.generation
     }
 }
 
+/// Sprites are drawn by layer, then from the top of the screen down so that
+/// things further south overlap the ones behind them, then by explicit z.
+/// `x` only breaks ties so the order is stable from frame to frame.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DrawOrder {
+    pub layer: i32,
+    pub y: i32,
+    pub z: i32,
+    pub x: i32,
+}
+
+/// The batch for a single atlas page.
 pub struct SpriteLayer {
-    tilemap: TileMap,
     pub batch: SpriteBatch,
 }
 
 impl SpriteLayer {
-    pub fn new(tilemap: TileMap) -> Self {
-        let image = tilemap.image.borrow().0.clone();
+    pub fn new(image: graphics::Image) -> Self {
         let mut batch = SpriteBatch::new(image);
         batch.set_filter(FilterMode::Nearest);
 
         SpriteLayer {
-            tilemap,
             batch,
         }
     }
 
-    pub fn add(&mut self, tile: &Tile, x: i32, y: i32, x_offset: f32, y_offset: f32) -> SpriteIdx {
-        let x: usize = x as usize * self.tilemap.sprite_dimensions as usize;
-        let y: usize = y as usize * self.tilemap.sprite_dimensions as usize;
+    pub fn clear(&mut self) {
+        self.batch.clear();
+    }
+}
 
-        let sprite_x = tile.sprite_id as usize % self.tilemap.tile_width as usize;
-        let sprite_y = tile.sprite_id as usize / self.tilemap.tile_width as usize;
+/// Collects the sprites of a frame, creating a `SpriteLayer` the first time
+/// an atlas page is used. Sorting happens across pages, so consecutive
+/// sprites on the same page become one draw call.
+pub struct SpriteLayers {
+    screen: Screen,
+    /// Generation of the atlas the layers' images were taken from.
+    generation: usize,
+    layers: HashMap<usize, SpriteLayer>,
+    queued: Vec<(DrawOrder, usize, DrawParam)>,
+}
+
+impl SpriteLayers {
+    pub fn new(screen: Screen) -> Self {
+        SpriteLayers {
+            screen,
+            generation: 0,
+            layers: HashMap::new(),
+            queued: Vec::new(),
+        }
+    }
+
+    /// Queues a sprite at a grid position, moved, scaled, rotated and tinted
+    /// by `tween`. Frames are anchored to the bottom center of their cell, so
+    /// frames taller or wider than a tile stick out above and to the sides,
+    /// and scaling and rotation happen around that anchor too.
+    pub fn add(
+        &mut self,
+        tilesets: &TileSetRegistry,
+        tile: &Tile,
+        x: i32,
+        y: i32,
+        tween: &TweenState,
+        z: i32,
+    ) {
+        if self.generation != tilesets.generation() {
+            self.generation = tilesets.generation();
+            self.layers.clear();
+            self.queued.clear();
+        }
+
+        let tileset = match tilesets.get(&tile.tileset) {
+            Some(tileset) => tileset,
+            None => return,
+        };
+
+        let region = match tileset.region(tile.sprite_id) {
+            Some(region) => region,
+            None => return,
+        };
+
+        let tile_size = TILE_SIZE as f32;
+        let cell_x = (x as f32 + tween.offset.0) * tile_size;
+        let cell_y = (y as f32 + tween.offset.1) * tile_size;
+        let (frame_w, frame_h) = tileset.frame_size();
+        let (scale_x, scale_y) = tween.scale;
+
+        let draw_order = DrawOrder {
+            layer: tile.sprite_layer,
+            y: cell_y.round() as i32,
+            z,
+            x: cell_x.round() as i32,
+        };
+
+        // ggez rotates around `dest`, so work out where the top left corner
+        // ends up when the anchor stays put.
+        let (anchor_x, anchor_y) = (cell_x + tile_size / 2.0, cell_y + tile_size);
+        let (corner_x, corner_y) = (-frame_w * scale_x / 2.0, -frame_h * scale_y);
+        let (sin, cos) = tween.rotation.sin_cos();
 
         let draw_param = DrawParam {
-            src: Rect::new(
-                (1.0 / self.tilemap.tile_width as f32) * sprite_x as f32,
-                (1.0 / self.tilemap.tile_height as f32) * sprite_y as f32,
-                1.0 / self.tilemap.tile_width as f32,
-                1.0 / self.tilemap.tile_height as f32,
+            src: region.src,
+            dest: self.screen.to_screen_coordinates(Point2::new(
+                anchor_x + corner_x * cos - corner_y * sin,
+                anchor_y + corner_x * sin + corner_y * cos,
+            )),
+            rotation: tween.rotation,
+            scale: Point2::new(
+                self.screen.scale_w * tileset.scale * scale_x,
+                self.screen.scale_h * tileset.scale * scale_y,
             ),
-            dest: self.tilemap
-                .screen
-                // @todo(vy): make cleaner
-                .to_screen_coordinates(Point2::new(x as f32 + (x_offset * self.tilemap.sprite_dimensions as f32), y as f32 + (y_offset * self.tilemap.sprite_dimensions as f32))),
-            scale: self.tilemap.scale,
-            color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+            color: Some(Color::new(tween.color[0], tween.color[1], tween.color[2], tween.alpha)),
             ..Default::default()
         };
 
-        self.batch.add(draw_param)
+        if !self.layers.contains_key(&region.page) {
+            let image = tilesets.atlas().pages[region.page].clone();
+            self.layers.insert(region.page, SpriteLayer::new(image));
+        }
+
+        self.queued.push((draw_order, region.page, draw_param));
     }
 
-    pub fn clear(&mut self) {
-        self.batch.clear();
+    /// Sorts everything queued by `DrawOrder` and draws it, batching runs of
+    /// sprites from the same atlas page.
+    pub fn draw(&mut self, ctx: &mut Context) -> GameResult<()> {
+        self.queued.sort_by_key(|&(draw_order, _, _)| draw_order);
+
+        let mut current = None;
+
+        for (_, page, draw_param) in self.queued.drain(..) {
+            if current != Some(page) {
+                if let Some(previous) = current {
+                    flush_layer(ctx, self.layers.get_mut(&previous))?;
+                }
+
+                current = Some(page);
+            }
+
+            if let Some(layer) = self.layers.get_mut(&page) {
+                layer.batch.add(draw_param);
+            }
+        }
+
+        if let Some(previous) = current {
+            flush_layer(ctx, self.layers.get_mut(&previous))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_screen(&mut self, screen: Screen) {
+        self.screen = screen;
     }
 }
+
+fn flush_layer(ctx: &mut Context, layer: Option<&mut SpriteLayer>) -> GameResult<()> {
+    let layer = match layer {
+        Some(layer) => layer,
+        None => return Ok(()),
+    };
+
+    let draw_param = DrawParam {
+        color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+        ..Default::default()
+    };
+
+    graphics::draw_ex(ctx, &layer.batch, draw_param)?;
+    layer.clear();
+
+    Ok(())
+}
@@ -0,0 +1,213 @@
+use std::f32::consts::PI;
+use std::time::Duration;
+
+/// Maps linear progress from 0 to 1 onto a curve. `Back` overshoots past the
+/// ends and `Bounce` settles like a dropped ball.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+    BounceIn,
+    BounceOut,
+    /// Loops out and back, for pulses that end where they started.
+    Sine,
+}
+
+/// How far `Back` easings overshoot, the usual value from Robert Penner's
+/// equations.
+const BACK_OVERSHOOT: f32 = 1.70158;
+
+impl Easing {
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.max(0.0).min(1.0);
+
+        match *self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::BackIn => back_in(t),
+            Easing::BackOut => 1.0 - back_in(1.0 - t),
+            Easing::BackInOut => {
+                if t < 0.5 {
+                    back_in(t * 2.0) / 2.0
+                } else {
+                    1.0 - back_in(2.0 - t * 2.0) / 2.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::Sine => (t * PI).sin(),
+        }
+    }
+}
+
+fn back_in(t: f32) -> f32 {
+    t * t * ((BACK_OVERSHOOT + 1.0) * t - BACK_OVERSHOOT)
+}
+
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// What a tween changes, from one value to another.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TweenProperty {
+    /// Offset from the entity's grid position, in tiles.
+    Offset { from: (f32, f32), to: (f32, f32) },
+    Scale { from: (f32, f32), to: (f32, f32) },
+    /// In radians, around the bottom center of the sprite.
+    Rotation { from: f32, to: f32 },
+    /// Tint as RGB.
+    Color { from: [f32; 3], to: [f32; 3] },
+    Alpha { from: f32, to: f32 },
+}
+
+/// Start and end are in game time, see `GameClock`.
+#[derive(Debug, Copy, Clone)]
+pub struct Tween {
+    pub property: TweenProperty,
+    pub easing: Easing,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+impl Tween {
+    pub fn new(property: TweenProperty, easing: Easing, start: Duration, duration: Duration) -> Tween {
+        Tween {
+            property,
+            easing,
+            start,
+            end: start + duration,
+        }
+    }
+
+    /// Grows from nothing with a slight overshoot, e.g. when a plant sprouts.
+    pub fn pop(start: Duration) -> Tween {
+        Tween::new(
+            TweenProperty::Scale {
+                from: (0.0, 0.0),
+                to: (1.0, 1.0),
+            },
+            Easing::BackOut,
+            start,
+            Duration::from_millis(250),
+        )
+    }
+
+    /// Nudges a third of a tile towards `direction` and back, for moves
+    /// into something that doesn't give way.
+    pub fn bump(direction: (i32, i32), start: Duration) -> Tween {
+        Tween::new(
+            TweenProperty::Offset {
+                from: (0.0, 0.0),
+                to: (direction.0 as f32 / 3.0, direction.1 as f32 / 3.0),
+            },
+            Easing::Sine,
+            start,
+            Duration::from_millis(150),
+        )
+    }
+
+    pub fn is_finished(&self, now: Duration) -> bool {
+        now >= self.end
+    }
+
+    /// Eased progress at `now`, or `None` outside of the tween's time span.
+    pub fn progress(&self, now: Duration) -> Option<f32> {
+        if now < self.start || now >= self.end {
+            return None;
+        }
+
+        let total = duration_to_secs(self.end - self.start);
+
+        if total <= 0.0 {
+            return None;
+        }
+
+        Some(self.easing.apply(duration_to_secs(now - self.start) / total))
+    }
+}
+
+/// The combined effect of the tweens playing on an entity. Offsets and
+/// rotations add up, scales and colors multiply.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TweenState {
+    pub offset: (f32, f32),
+    pub scale: (f32, f32),
+    pub rotation: f32,
+    pub color: [f32; 3],
+    pub alpha: f32,
+}
+
+impl Default for TweenState {
+    fn default() -> Self {
+        TweenState {
+            offset: (0.0, 0.0),
+            scale: (1.0, 1.0),
+            rotation: 0.0,
+            color: [1.0, 1.0, 1.0],
+            alpha: 1.0,
+        }
+    }
+}
+
+impl TweenState {
+    pub fn apply(&mut self, tween: &Tween, now: Duration) {
+        let t = match tween.progress(now) {
+            Some(t) => t,
+            None => return,
+        };
+
+        match tween.property {
+            TweenProperty::Offset { from, to } => {
+                self.offset.0 += lerp(from.0, to.0, t);
+                self.offset.1 += lerp(from.1, to.1, t);
+            }
+            TweenProperty::Scale { from, to } => {
+                self.scale.0 *= lerp(from.0, to.0, t);
+                self.scale.1 *= lerp(from.1, to.1, t);
+            }
+            TweenProperty::Rotation { from, to } => self.rotation += lerp(from, to, t),
+            TweenProperty::Color { from, to } => {
+                for channel in 0..3 {
+                    self.color[channel] *= lerp(from[channel], to[channel], t);
+                }
+            }
+            TweenProperty::Alpha { from, to } => self.alpha *= lerp(from, to, t),
+        }
+    }
+}
+
+fn lerp(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
+fn duration_to_secs(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 / 1_000_000_000.0
+}
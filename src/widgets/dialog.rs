@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ggez::graphics::{Color, DrawParam, Font, Point2, Rect, Scale, TextCached, TextFragment};
+use ggez::timer;
+use ggez::{Context, GameResult};
+
+use gui::{DialogEvent, Renderable, Widget, WidgetEvent};
+use input::Buttons;
+use screen::Screen;
+use widgets::panel::NineSlice;
+
+const DIALOG_WIDTH: f32 = 192.0;
+const DIALOG_PADDING: f32 = 10.0;
+const DIALOG_MARGIN: f32 = 4.0;
+const LINES_PER_PAGE: usize = 3;
+const CHARS_PER_SECOND: f32 = 40.0;
+
+/// A speech bubble drawn above a tile on the grid. Text is word wrapped to
+/// the bubble width, split into pages and revealed one character at a time;
+/// the Action button skips the reveal, then advances to the next page.
+pub struct Dialog {
+    panel: NineSlice,
+    font: Font,
+    text_scale: Scale,
+    screen: Screen,
+    tile_size: f32,
+    anchor: (i32, i32),
+    pages: Vec<String>,
+    page_index: usize,
+    revealed: f32,
+    line_height: f32,
+}
+
+impl Dialog {
+    pub fn new(
+        ctx: &mut Context,
+        text: &str,
+        panel: NineSlice,
+        font: Font,
+        text_scale: Scale,
+        screen: Screen,
+        tile_size: u32,
+        anchor: (i32, i32),
+    ) -> GameResult<Box<Dialog>> {
+        let max_width = (DIALOG_WIDTH - DIALOG_PADDING * 2.0) * screen.scale_w;
+        let lines = wrap_text(ctx, text, &font, text_scale, max_width)?;
+        let pages = lines
+            .chunks(LINES_PER_PAGE)
+            .map(|page| page.join("\n"))
+            .collect();
+
+        let line_height = text_fragment("Ag", &font, text_scale)?.height(ctx) as f32 / screen.scale_h;
+
+        Ok(Box::new(Dialog {
+            panel,
+            font,
+            text_scale,
+            screen,
+            tile_size: tile_size as f32,
+            anchor,
+            pages,
+            page_index: 0,
+            revealed: 0.0,
+            line_height,
+        }))
+    }
+
+    pub fn set_anchor(&mut self, x: i32, y: i32) {
+        self.anchor = (x, y);
+    }
+
+    /// Wrapping is measured in screen pixels, but the text and the bubble
+    /// scale together, so the existing line breaks stay valid.
+    pub fn resize(&mut self, screen: Screen, text_scale: Scale) {
+        self.screen = screen;
+        self.text_scale = text_scale;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.page_index >= self.pages.len()
+    }
+
+    fn current_page(&self) -> &str {
+        self.pages.get(self.page_index).map(|page| page.as_str()).unwrap_or("")
+    }
+
+    fn page_revealed(&self) -> bool {
+        self.revealed as usize >= self.current_page().chars().count()
+    }
+
+    /// Bubble bounds in logical coordinates, centered above the anchor tile.
+    fn bounds(&self) -> Rect {
+        let (anchor_x, anchor_y) = self.anchor;
+        let height = self.line_height * LINES_PER_PAGE as f32 + DIALOG_PADDING * 2.0;
+
+        Rect::new(
+            anchor_x as f32 * self.tile_size + self.tile_size / 2.0 - DIALOG_WIDTH / 2.0,
+            anchor_y as f32 * self.tile_size - height - DIALOG_MARGIN,
+            DIALOG_WIDTH,
+            height,
+        )
+    }
+}
+
+impl Renderable for Dialog {
+    fn render(&self, ctx: &mut Context) -> GameResult<()> {
+        if self.is_finished() {
+            return Ok(());
+        }
+
+        let bounds = self.bounds();
+        let origin = self.screen.to_screen_coordinates(Point2::new(bounds.x, bounds.y));
+        let dest = Rect::new(
+            origin.x,
+            origin.y,
+            bounds.w * self.screen.scale_w,
+            bounds.h * self.screen.scale_h,
+        );
+
+        self.panel.draw(ctx, dest, Point2::new(self.screen.scale_w, self.screen.scale_h))?;
+
+        let visible: String = self.current_page().chars().take(self.revealed as usize).collect();
+        let text = text_fragment(&visible, &self.font, self.text_scale)?;
+        let text_color = Some(Color::new(0.25, 0.15, 0.1, 1.0));
+
+        text.queue(
+            ctx,
+            self.screen.to_screen_coordinates(Point2::new(bounds.x + DIALOG_PADDING, bounds.y + DIALOG_PADDING)),
+            text_color,
+        );
+
+        if self.page_revealed() && self.page_index + 1 < self.pages.len() {
+            let more = text_fragment(">", &self.font, self.text_scale)?;
+
+            more.queue(
+                ctx,
+                self.screen.to_screen_coordinates(Point2::new(
+                    bounds.right() - DIALOG_PADDING,
+                    bounds.bottom() - DIALOG_PADDING - self.line_height,
+                )),
+                text_color,
+            );
+        }
+
+        TextCached::draw_queued(ctx, DrawParam::default())
+    }
+}
+
+impl Widget for Dialog {
+    fn interact(&mut self, interaction: Buttons, messages: &mut VecDeque<WidgetEvent>) {
+        if interaction != Buttons::Action || self.is_finished() {
+            return;
+        }
+
+        if !self.page_revealed() {
+            self.revealed = self.current_page().chars().count() as f32;
+            return;
+        }
+
+        self.page_index += 1;
+        self.revealed = 0.0;
+
+        if self.is_finished() {
+            messages.push_back(WidgetEvent::DialogEvent(DialogEvent::Finished));
+        } else {
+            messages.push_back(WidgetEvent::DialogEvent(DialogEvent::PageTurned(self.page_index)));
+        }
+    }
+
+    fn update(&mut self, dt: Duration) {
+        if self.is_finished() {
+            return;
+        }
+
+        let page_length = self.current_page().chars().count() as f32;
+        let revealed = self.revealed + timer::duration_to_f64(dt) as f32 * CHARS_PER_SECOND;
+
+        self.revealed = revealed.min(page_length);
+    }
+}
+
+fn text_fragment(text: &str, font: &Font, scale: Scale) -> GameResult<TextCached> {
+    TextCached::new(TextFragment {
+        text: text.to_string(),
+        font_id: Some(font.clone().into()),
+        scale: Some(scale),
+        ..Default::default()
+    })
+}
+
+/// Greedily breaks `text` into lines no wider than `max_width` screen pixels.
+/// Explicit newlines are kept as line breaks.
+fn wrap_text(ctx: &Context, text: &str, font: &Font, scale: Scale, max_width: f32) -> GameResult<Vec<String>> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let mut line = String::new();
+
+        for word in paragraph.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if !line.is_empty() && text_fragment(&candidate, font, scale)?.width(ctx) as f32 > max_width {
+                lines.push(line);
+                line = word.to_string();
+            } else {
+                line = candidate;
+            }
+        }
+
+        lines.push(line);
+    }
+
+    Ok(lines)
+}
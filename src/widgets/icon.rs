@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use ggez::graphics::{self, Color, DrawParam, Point2};
+use ggez::{Context, GameResult};
+
+use animated_gif::AnimatedGif;
+use gui::{Renderable, Widget, WidgetEvent};
+use input::Buttons;
+
+/// A looping animated image, e.g. the stopwatch in the HUD.
+pub struct AnimatedIcon {
+    animation: AnimatedGif,
+    position: Point2,
+    scale: Point2,
+    elapsed: Duration,
+}
+
+impl AnimatedIcon {
+    pub fn new(animation: AnimatedGif, position: Point2, scale: Point2) -> Box<AnimatedIcon> {
+        Box::new(AnimatedIcon {
+            animation,
+            position,
+            scale,
+            elapsed: Duration::from_millis(0),
+        })
+    }
+}
+
+impl Renderable for AnimatedIcon {
+    fn render(&self, ctx: &mut Context) -> GameResult<()> {
+        let frame = self.animation.clip.frame_at(self.elapsed, true);
+
+        let draw_param = DrawParam {
+            src: self.animation.src(frame),
+            dest: self.position,
+            scale: self.scale,
+            color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+            ..Default::default()
+        };
+
+        graphics::draw_ex(ctx, &self.animation.strip, draw_param)
+    }
+}
+
+impl Widget for AnimatedIcon {
+    fn interact(&mut self, _interaction: Buttons, _messages: &mut VecDeque<WidgetEvent>) {}
+
+    fn update(&mut self, dt: Duration) {
+        self.elapsed += dt;
+    }
+}
@@ -1,42 +1,106 @@
+use std::cell::RefCell;
 use std::collections::VecDeque;
 use std::rc::Rc;
 
-use ggez::graphics;
+use ggez::graphics::{self, Color, DrawParam, Font, Scale, TextFragment};
 use ggez::{Context, GameResult};
 
-use gui::{Renderable, Widget, WidgetEvent};
+use gui::{MenuEvent, Renderable, Widget, WidgetEvent};
 use input::Buttons;
-use state::Store;
+use state::{Action, MenuAction, Store};
 
 pub struct Menu {
     menu_options: Vec<String>,
     x: f32,
     y: f32,
     spacing: f32,
-    store: Rc<Store>,
+    font: Font,
+    scale: Scale,
+    store: Rc<RefCell<Store>>,
 }
 
 impl Menu {
-    pub fn new(x: f32, y: f32, spacing: f32, store: Rc<Store>) -> Box<Menu> {
+    pub fn new(x: f32, y: f32, spacing: f32, font: Font, scale: Scale, store: Rc<RefCell<Store>>) -> Box<Menu> {
         Box::new(Menu {
             menu_options: Vec::new(),
             x,
             y,
             spacing,
+            font,
+            scale,
             store,
         })
     }
+
+    pub fn with_option(mut self: Box<Self>, option: &str) -> Box<Menu> {
+        self.menu_options.push(option.to_string());
+        self
+    }
+
+    fn selected_index(&self) -> i32 {
+        self.store.borrow().selected_menu_item_index
+    }
 }
 
 impl Renderable for Menu {
     fn render(&self, ctx: &mut Context) -> GameResult<()> {
-        let text = graphics::TextCached::new("MenuWidget")?;
-        text.queue(ctx, graphics::Point2::new(self.x, self.y), None);
+        let selected_index = self.selected_index();
+
+        for (index, option) in self.menu_options.iter().enumerate() {
+            let selected = index as i32 == selected_index;
+            let (label, color) = if selected {
+                (format!("> {}", option), Color::new(1.0, 1.0, 1.0, 1.0))
+            } else {
+                (format!("  {}", option), Color::new(0.6, 0.6, 0.6, 1.0))
+            };
+
+            let text = graphics::TextCached::new(TextFragment {
+                text: label,
+                font_id: Some(self.font.clone().into()),
+                scale: Some(self.scale),
+                ..Default::default()
+            })?;
 
-        Ok(())
+            text.queue(
+                ctx,
+                graphics::Point2::new(self.x, self.y + self.spacing * index as f32),
+                Some(color),
+            );
+        }
+
+        graphics::TextCached::draw_queued(ctx, DrawParam::default())
     }
 }
 
 impl Widget for Menu {
-    fn interact(&self, _interaction: Buttons, _messages: &mut VecDeque<WidgetEvent>) {}
+    fn interact(&mut self, interaction: Buttons, messages: &mut VecDeque<WidgetEvent>) {
+        let option_count = self.menu_options.len() as i32;
+
+        if option_count == 0 {
+            return;
+        }
+
+        let selected_index = self.selected_index();
+
+        match interaction {
+            Buttons::Up => {
+                let index = (selected_index - 1 + option_count) % option_count;
+                self.store
+                    .borrow_mut()
+                    .dispatch(Action::Menus(MenuAction::SetActiveMenuItemIndex(index)));
+                messages.push_back(WidgetEvent::MenuEvent(MenuEvent::Moved(index)));
+            }
+            Buttons::Down => {
+                let index = (selected_index + 1) % option_count;
+                self.store
+                    .borrow_mut()
+                    .dispatch(Action::Menus(MenuAction::SetActiveMenuItemIndex(index)));
+                messages.push_back(WidgetEvent::MenuEvent(MenuEvent::Moved(index)));
+            }
+            Buttons::Action => {
+                messages.push_back(WidgetEvent::MenuEvent(MenuEvent::Selected(selected_index)));
+            }
+            _ => {}
+        }
+    }
 }
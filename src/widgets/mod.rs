@@ -1 +1,4 @@
+pub mod dialog;
+pub mod icon;
 pub mod menu;
+pub mod panel;
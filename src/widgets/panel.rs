@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+use ggez::graphics::{self, Color, DrawParam, FilterMode, Point2, Rect};
+use ggez::{Context, GameResult};
+
+use gui::{Renderable, Widget, WidgetEvent};
+use input::Buttons;
+
+/// Splits an image into a 3x3 grid so it can be stretched to any size:
+/// corners are drawn as-is, edges stretch along one axis and the center
+/// stretches along both.
+#[derive(Clone)]
+pub struct NineSlice {
+    image: graphics::Image,
+    border: f32,
+}
+
+impl NineSlice {
+    pub fn new(mut image: graphics::Image, border: u32) -> NineSlice {
+        image.set_filter(FilterMode::Nearest);
+
+        NineSlice {
+            image,
+            border: border as f32,
+        }
+    }
+
+    /// Smallest size the panel can be drawn at without the corners overlapping.
+    pub fn min_size(&self, scale: Point2) -> (f32, f32) {
+        (self.border * 2.0 * scale.x, self.border * 2.0 * scale.y)
+    }
+
+    /// Draws the panel to fill `dest` (in screen coordinates), with one
+    /// source pixel covering `scale` screen pixels in the corners.
+    pub fn draw(&self, ctx: &mut Context, dest: Rect, scale: Point2) -> GameResult<()> {
+        let image_w = self.image.width() as f32;
+        let image_h = self.image.height() as f32;
+
+        let src_x = [0.0, self.border, image_w - self.border];
+        let src_w = [self.border, image_w - self.border * 2.0, self.border];
+        let src_y = [0.0, self.border, image_h - self.border];
+        let src_h = [self.border, image_h - self.border * 2.0, self.border];
+
+        let (min_w, min_h) = self.min_size(scale);
+        let center_w = (dest.w - min_w).max(0.0);
+        let center_h = (dest.h - min_h).max(0.0);
+
+        let dest_x = [dest.x, dest.x + self.border * scale.x, dest.x + self.border * scale.x + center_w];
+        let dest_w = [self.border * scale.x, center_w, self.border * scale.x];
+        let dest_y = [dest.y, dest.y + self.border * scale.y, dest.y + self.border * scale.y + center_h];
+        let dest_h = [self.border * scale.y, center_h, self.border * scale.y];
+
+        for row in 0..3 {
+            for column in 0..3 {
+                if src_w[column] <= 0.0 || src_h[row] <= 0.0 {
+                    continue;
+                }
+
+                let draw_param = DrawParam {
+                    src: Rect::new(
+                        src_x[column] / image_w,
+                        src_y[row] / image_h,
+                        src_w[column] / image_w,
+                        src_h[row] / image_h,
+                    ),
+                    dest: Point2::new(dest_x[column], dest_y[row]),
+                    scale: Point2::new(dest_w[column] / src_w[column], dest_h[row] / src_h[row]),
+                    color: Some(Color::new(1.0, 1.0, 1.0, 1.0)),
+                    ..Default::default()
+                };
+
+                graphics::draw_ex(ctx, &self.image, draw_param)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Panel {
+    nine_slice: NineSlice,
+    rect: Rect,
+    scale: Point2,
+}
+
+impl Panel {
+    pub fn new(nine_slice: NineSlice, rect: Rect, scale: Point2) -> Box<Panel> {
+        Box::new(Panel {
+            nine_slice,
+            rect,
+            scale,
+        })
+    }
+}
+
+impl Renderable for Panel {
+    fn render(&self, ctx: &mut Context) -> GameResult<()> {
+        self.nine_slice.draw(ctx, self.rect, self.scale)
+    }
+}
+
+impl Widget for Panel {
+    fn interact(&mut self, _interaction: Buttons, _messages: &mut VecDeque<WidgetEvent>) {}
+}
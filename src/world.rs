@@ -12,8 +12,14 @@ impl World {
         self.specs_world.register::<Position>();
         self.specs_world.register::<Plantae>();
         self.specs_world.register::<Solid>();
+        self.specs_world.register::<Pushable>();
+        self.specs_world.register::<Fixture>();
         self.specs_world.register::<Sprite>();
+        self.specs_world.register::<Shadow>();
         self.specs_world.register::<Movement>();
+        self.specs_world.register::<Creature>();
+        self.specs_world.register::<Trigger>();
+        self.specs_world.register::<Reactor>();
         self.specs_world.register::<Animation>();
     }
 